@@ -0,0 +1,132 @@
+// the work done for every key press before a bind can be looked up: reading
+// the held modifiers and matching them against the modifiers of a bind, and
+// the whole lookup of the chord in a config.
+// `cargo bench -p hotkeyd`
+
+use std::{
+    collections::{BTreeSet, HashSet},
+    hint::black_box,
+    sync::RwLock,
+};
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use rdev::EventType;
+
+use hotkeyd::{
+    app::FocusedAppProvider,
+    config::Bind,
+    config_manager::{ConfigManager, Lookup},
+    key::{
+        ButtonSet, HeldModifiers, KeyboardKey, ModifierAlias, ModifierKey, ModifierSet, Modifiers,
+        Trigger,
+    },
+    simulate::EventSink,
+};
+
+const HELD: [ModifierKey; 3] = [
+    ModifierKey::MetaLeft,
+    ModifierKey::ShiftLeft,
+    ModifierKey::ControlLeft,
+];
+
+fn held_modifiers(c: &mut Criterion) {
+    let mut group = c.benchmark_group("held modifiers");
+
+    // how they were tracked before, a set behind a lock copied on every press
+    let locked: RwLock<HashSet<ModifierKey>> = RwLock::new(HELD.into_iter().collect());
+    group.bench_function("rwlock hashset", |b| {
+        b.iter(|| {
+            let held = locked.read().unwrap();
+            black_box(held.iter().copied().collect::<BTreeSet<ModifierKey>>())
+        })
+    });
+
+    let atomic = HeldModifiers::default();
+    for modifier in HELD {
+        atomic.press(modifier);
+    }
+    group.bench_function("bitset", |b| b.iter(|| black_box(atomic.get())));
+
+    group.finish();
+}
+
+fn matches(c: &mut Criterion) {
+    let held: ModifierSet<ModifierKey> = HELD.into_iter().collect();
+
+    // every modifier as an alias, the most work a match can be
+    let bind = Modifiers {
+        exact: ModifierSet::new(),
+        aliases: [
+            ModifierAlias::Meta,
+            ModifierAlias::Shift,
+            ModifierAlias::Ctrl,
+        ]
+        .into_iter()
+        .collect(),
+    };
+
+    c.bench_function("alias match", |b| {
+        b.iter(|| black_box(&bind).matches(black_box(held)))
+    });
+}
+
+struct NoFocusedApp;
+
+impl FocusedAppProvider for NoFocusedApp {
+    fn focused_app(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+// nothing is run so nothing is ever sent
+struct NoSink;
+
+impl EventSink for NoSink {
+    fn send(&self, _event_type: EventType) {}
+}
+
+fn exec(c: &mut Criterion) {
+    // sequences that all start on `key-k`, half written exactly and half
+    // with aliases, so every one of them is a candidate for the chord. the
+    // same sequence can't be written both ways since those would overlap.
+    let mut config = String::new();
+    for last in 'a'..='z' {
+        let first = if last <= 'm' {
+            "meta-left + shift-left + control-left + key-k"
+        } else {
+            "meta + shift + ctrl + key-k"
+        };
+        config.push_str(&format!(
+            "[binds.\"{}, key-{}\"]\ntype = \"cmd\"\ncommand = \"true\"\n",
+            first, last
+        ));
+    }
+    let manager: &'static ConfigManager = Box::leak(Box::new(ConfigManager::new_from_str(
+        &config,
+        Box::new(NoFocusedApp),
+    )));
+
+    let chord = |held: ModifierSet<ModifierKey>| {
+        [Bind::new(
+            held,
+            ButtonSet::new(),
+            Trigger::Keyboard(KeyboardKey::KeyK),
+        )]
+    };
+    let pending = chord(HELD.into_iter().collect());
+    let no_match = chord([ModifierKey::Alt].into_iter().collect());
+    assert!(matches!(manager.exec(&pending, &NoSink), Lookup::Pending));
+    assert!(matches!(manager.exec(&no_match, &NoSink), Lookup::NoMatch));
+
+    let mut group = c.benchmark_group("exec");
+    group.bench_function("pending", |b| {
+        b.iter(|| black_box(manager.exec(black_box(&pending), &NoSink)))
+    });
+    group.bench_function("no match", |b| {
+        b.iter(|| black_box(manager.exec(black_box(&no_match), &NoSink)))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, held_modifiers, matches, exec);
+criterion_main!(benches);
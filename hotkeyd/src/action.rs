@@ -0,0 +1,926 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    ops::Range,
+    path::PathBuf,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use rdev::EventType;
+use serde::{Deserialize, Serialize};
+use toml::{Table, Value};
+
+use crate::{
+    config::{deserialize_entry, EntryError, PathPart},
+    executor::{Concurrency, Job, Process, Program, Runner},
+    key::KeyStroke,
+    simulate::EventSink,
+    utils::expand_path,
+};
+
+// an action as it is written in the config, before the keys are parsed. the
+// `type` is pulled out of the table first, see `Action::from_config`.
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+enum RawAction {
+    Cmd {
+        // either `command`, which is run by `shell`, or `args`
+        command: Option<String>,
+        args: Option<Vec<String>>,
+        shell: Option<String>,
+        cwd: Option<String>,
+        #[serde(default)]
+        env: BTreeMap<String, String>,
+        #[serde(default)]
+        clear_env: bool,
+        #[serde(default)]
+        concurrency: Concurrency,
+        timeout_ms: Option<u64>,
+        stdout: Option<String>,
+    },
+    Keys {
+        keys: Vec<String>,
+    },
+    Mode {
+        name: String,
+    },
+    // nested actions are parsed on their own so errors in them keep their
+    // path
+    Sequence {
+        actions: Vec<Value>,
+    },
+    Sleep {
+        duration_ms: u64,
+    },
+    // the probe is written like a `cmd`
+    If {
+        command: Option<String>,
+        args: Option<Vec<String>>,
+        shell: Option<String>,
+        cwd: Option<String>,
+        #[serde(default)]
+        env: BTreeMap<String, String>,
+        #[serde(default)]
+        clear_env: bool,
+        timeout_ms: Option<u64>,
+        then: Option<Value>,
+        #[serde(rename = "else")]
+        otherwise: Option<Value>,
+    },
+}
+
+// tells the runs of one action apart from those of every other action, even
+// ones that run the same command. every `cmd` and `if` gets its own as the
+// config is parsed so a reloaded config starts over with new ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ActionId(u64);
+
+impl ActionId {
+    pub fn next() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        ActionId(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Action {
+    Cmd {
+        id: ActionId,
+        process: Process,
+        concurrency: Concurrency,
+        timeout: Option<Duration>,
+    },
+    Keys {
+        strokes: Vec<KeyStroke>,
+    },
+    // switches to the mode `name`
+    Mode {
+        name: String,
+    },
+    // runs every action in order
+    Sequence {
+        actions: Vec<Action>,
+    },
+    Sleep {
+        duration: Duration,
+    },
+    // runs `probe` and waits for it, `then` runs if it exits with 0 and
+    // `otherwise` if it doesn't (or times out)
+    If {
+        id: ActionId,
+        probe: Process,
+        timeout: Option<Duration>,
+        then: Option<Box<Action>>,
+        otherwise: Option<Box<Action>>,
+    },
+}
+
+// how deep `sequence` and `if` actions can be nested
+const MAX_DEPTH: usize = 8;
+
+// what an action needs while it runs
+pub struct RunContext<'a> {
+    // names what triggered the action in the logs
+    pub bind: &'a str,
+    // extra environment variables describing what triggered the action
+    // (scroll deltas, etc). only commands see them.
+    pub envs: &'a [(String, String)],
+    pub sink: &'a dyn EventSink,
+    pub runner: &'a dyn Runner,
+    // modes belong to the config manager
+    pub set_mode: &'a dyn Fn(&str),
+}
+
+impl RunContext<'_> {
+    fn job(
+        &self,
+        action: ActionId,
+        process: &Process,
+        concurrency: Concurrency,
+        timeout: Option<Duration>,
+    ) -> Job {
+        Job {
+            action,
+            process: process.clone(),
+            envs: self.envs.to_vec(),
+            concurrency,
+            timeout,
+            bind: self.bind.to_string(),
+        }
+    }
+}
+
+impl Action {
+    // an action table of the config. serde can't tell which field of an
+    // internally tagged enum was wrong so `type` is taken out and the rest of
+    // the table is parsed as the variant it names.
+    pub fn from_config(value: Value) -> Result<Self, EntryError> {
+        let Value::Table(mut table) = value else {
+            return Err("an action has to be a table".to_string().into());
+        };
+        let kind = match table.remove("type") {
+            Some(Value::String(kind)) => kind,
+            Some(_) => {
+                return Err(EntryError::at(
+                    "type",
+                    "`type` has to be a string".to_string(),
+                ))
+            }
+            None => return Err("missing field `type`".to_string().into()),
+        };
+
+        let tagged = Value::Table(Table::from_iter([(kind, Value::Table(table))]));
+        let raw: RawAction = deserialize_entry(tagged).map_err(|mut error| {
+            if error.path.is_empty() && error.message.starts_with("unknown variant") {
+                error.path.push(PathPart::Key("type".to_string()));
+            }
+            error
+        })?;
+        Action::try_from(raw)
+    }
+
+    // `cmd` actions are handed to the runner instead of being waited for,
+    // `sleep` and the probe of `if` block until they are done
+    pub fn execute(&self, cx: &RunContext) {
+        match self {
+            Action::Keys { strokes } => {
+                for stroke in strokes {
+                    for modifier in stroke.modifiers.iter() {
+                        cx.sink.send(EventType::KeyPress(modifier.to_rdev()));
+                    }
+                    cx.sink.send(EventType::KeyPress(stroke.key.to_rdev()));
+                    cx.sink.send(EventType::KeyRelease(stroke.key.to_rdev()));
+                    for modifier in stroke.modifiers.iter().rev() {
+                        cx.sink.send(EventType::KeyRelease(modifier.to_rdev()));
+                    }
+                }
+            }
+            Action::Mode { name } => (cx.set_mode)(name),
+            Action::Cmd {
+                id,
+                process,
+                concurrency,
+                timeout,
+            } => cx.runner.run(cx.job(*id, process, *concurrency, *timeout)),
+            Action::Sequence { actions } => {
+                for action in actions {
+                    action.execute(cx);
+                }
+            }
+            Action::Sleep { duration } => cx.runner.sleep(*duration),
+            Action::If {
+                id,
+                probe,
+                timeout,
+                then,
+                otherwise,
+            } => {
+                let job = cx.job(*id, probe, Concurrency::Allow, *timeout);
+                let branch = match cx.runner.probe(job) {
+                    Some(0) => then,
+                    _ => otherwise,
+                };
+                if let Some(action) = branch {
+                    action.execute(cx);
+                }
+            }
+        }
+    }
+
+    // whether running the action blocks for a while, those can't run in the
+    // grab callback without holding up every key
+    pub fn waits(&self) -> bool {
+        match self {
+            Action::Sleep { .. } | Action::If { .. } => true,
+            Action::Sequence { actions } => actions.iter().any(Action::waits),
+            _ => false,
+        }
+    }
+
+    // whether the action types keys. those are the only ones the held
+    // modifiers of a bind can leak into.
+    pub fn types_keys(&self) -> bool {
+        match self {
+            Action::Keys { .. } => true,
+            Action::Sequence { actions } => actions.iter().any(Action::types_keys),
+            Action::If {
+                then, otherwise, ..
+            } => [then, otherwise]
+                .into_iter()
+                .flatten()
+                .any(|action| action.types_keys()),
+            _ => false,
+        }
+    }
+
+    // every mode the action can switch to
+    pub fn modes(&self) -> Vec<&str> {
+        match self {
+            Action::Mode { name } => vec![name.as_str()],
+            Action::Sequence { actions } => actions.iter().flat_map(Action::modes).collect(),
+            Action::If {
+                then, otherwise, ..
+            } => [then, otherwise]
+                .into_iter()
+                .flatten()
+                .flat_map(|action| action.modes())
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn depth(&self) -> usize {
+        match self {
+            Action::Sequence { actions } => {
+                1 + actions.iter().map(Action::depth).max().unwrap_or(0)
+            }
+            Action::If {
+                then, otherwise, ..
+            } => {
+                1 + [then, otherwise]
+                    .into_iter()
+                    .flatten()
+                    .map(|action| action.depth())
+                    .max()
+                    .unwrap_or(0)
+            }
+            _ => 1,
+        }
+    }
+}
+
+impl TryFrom<RawAction> for Action {
+    type Error = EntryError;
+
+    fn try_from(raw: RawAction) -> Result<Self, Self::Error> {
+        let keys = match raw {
+            RawAction::Cmd {
+                command,
+                args,
+                shell,
+                cwd,
+                env,
+                clear_env,
+                concurrency,
+                timeout_ms,
+                stdout,
+            } => {
+                return Ok(Action::Cmd {
+                    id: ActionId::next(),
+                    process: process_from_config(
+                        command, args, shell, cwd, env, clear_env, stdout,
+                    )?,
+                    concurrency,
+                    timeout: timeout_ms.map(Duration::from_millis),
+                });
+            }
+            RawAction::Mode { name } => return Ok(Action::Mode { name }),
+            RawAction::Sleep { duration_ms } => {
+                return Ok(Action::Sleep {
+                    duration: Duration::from_millis(duration_ms),
+                })
+            }
+            RawAction::Sequence { actions } => {
+                if actions.is_empty() {
+                    return Err(EntryError::at(
+                        "actions",
+                        "`sequence` needs at least one action".to_string(),
+                    ));
+                }
+                let actions = actions
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, action)| {
+                        Action::from_config(action).map_err(|error| {
+                            error
+                                .within(PathPart::Index(i))
+                                .within(PathPart::Key("actions".to_string()))
+                        })
+                    })
+                    .collect::<Result<_, _>>()?;
+                return nested(Action::Sequence { actions });
+            }
+            RawAction::If {
+                command,
+                args,
+                shell,
+                cwd,
+                env,
+                clear_env,
+                timeout_ms,
+                then,
+                otherwise,
+            } => {
+                if then.is_none() && otherwise.is_none() {
+                    return Err("`if` needs a `then` or an `else` action".to_string().into());
+                }
+                let branch = |action: Option<Value>, key: &str| {
+                    action
+                        .map(|action| {
+                            Action::from_config(action)
+                                .map(Box::new)
+                                .map_err(|error| error.within(PathPart::Key(key.to_string())))
+                        })
+                        .transpose()
+                };
+                return nested(Action::If {
+                    id: ActionId::next(),
+                    probe: process_from_config(command, args, shell, cwd, env, clear_env, None)?,
+                    timeout: timeout_ms.map(Duration::from_millis),
+                    then: branch(then, "then")?,
+                    otherwise: branch(otherwise, "else")?,
+                });
+            }
+            RawAction::Keys { keys } => keys,
+        };
+
+        // every entry is either a chord made of key names (`return`,
+        // `meta-left + key-c`) or text that is typed out as is (`:wa`)
+        let mut strokes = Vec::new();
+        for (i, entry) in keys.into_iter().enumerate() {
+            if let Some(stroke) = KeyStroke::from_config_kebab(&entry) {
+                if !stroke.key.exists() {
+                    let message = format!("`{}` doesn't exist on this platform", entry);
+                    return Err(EntryError::from(message)
+                        .within(PathPart::Index(i))
+                        .within(PathPart::Key("keys".to_string())));
+                }
+                strokes.push(stroke);
+                continue;
+            }
+
+            for c in entry.chars() {
+                let Some(stroke) = KeyStroke::from_char(c) else {
+                    let message = format!("don't know how to type {:?}", c);
+                    return Err(EntryError::from(message)
+                        .within(PathPart::Index(i))
+                        .within(PathPart::Key("keys".to_string())));
+                };
+                strokes.push(stroke);
+            }
+        }
+        Ok(Action::Keys { strokes })
+    }
+}
+
+// what `command` runs with unless `shell` says otherwise
+const DEFAULT_SHELL: &str = "sh";
+
+// the children were checked when they were parsed, so only the new level has
+// to be
+fn nested(action: Action) -> Result<Action, EntryError> {
+    if action.depth() > MAX_DEPTH {
+        return Err(format!(
+            "actions can't be nested more than {} levels deep",
+            MAX_DEPTH
+        )
+        .into());
+    }
+    Ok(action)
+}
+
+// the process of a `cmd` action or the probe of an `if`
+fn process_from_config(
+    command: Option<String>,
+    args: Option<Vec<String>>,
+    shell: Option<String>,
+    cwd: Option<String>,
+    env: BTreeMap<String, String>,
+    clear_env: bool,
+    stdout: Option<String>,
+) -> Result<Process, EntryError> {
+    let program = match (command, args) {
+        (Some(command), None) => Program::Shell {
+            shell: path_from_config("shell", shell.as_deref().unwrap_or(DEFAULT_SHELL))?,
+            command,
+        },
+        (None, Some(_)) if shell.is_some() => {
+            return Err(EntryError::at(
+                "shell",
+                "`shell` can't be used with `args`".to_string(),
+            ))
+        }
+        (None, Some(args)) if args.is_empty() => {
+            return Err(EntryError::at(
+                "args",
+                "`args` needs at least the program to run".to_string(),
+            ))
+        }
+        (None, Some(args)) => Program::Exec(args),
+        (Some(_), Some(_)) => {
+            return Err("`command` and `args` can't be used together"
+                .to_string()
+                .into())
+        }
+        (None, None) => return Err("either `command` or `args` is needed".to_string().into()),
+    };
+    Ok(Process {
+        program,
+        cwd: cwd.as_deref().map(cwd_from_config).transpose()?,
+        env: env_from_config(env)?,
+        clear_env,
+        stdout: stdout
+            .as_deref()
+            .map(|stdout| path_from_config("stdout", stdout))
+            .transpose()?
+            .map(PathBuf::from),
+    })
+}
+
+// `key` with `~` and environment variables expanded
+fn path_from_config(key: &str, path: &str) -> Result<String, EntryError> {
+    expand_path(path).map_err(|message| EntryError::at(key, message))
+}
+
+fn cwd_from_config(cwd: &str) -> Result<PathBuf, EntryError> {
+    let path = PathBuf::from(path_from_config("cwd", cwd)?);
+    if !path.is_dir() {
+        return Err(EntryError::at(
+            "cwd",
+            format!("`cwd` `{}` is not a directory", path.display()),
+        ));
+    }
+    Ok(path)
+}
+
+fn env_from_config(env: BTreeMap<String, String>) -> Result<Vec<(String, String)>, EntryError> {
+    for name in env.keys() {
+        if name.is_empty() || name.contains('=') || name.contains('\0') {
+            let message = format!("`{}` can't be the name of an environment variable", name);
+            return Err(EntryError::at(name, message).within(PathPart::Key("env".to_string())));
+        }
+    }
+    Ok(env.into_iter().collect())
+}
+
+// the [defaults] table, every field is one of a `cmd` action. it's parsed to
+// check it and turned back into a table to be merged into the actions.
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct Defaults {
+    shell: Option<String>,
+    cwd: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    env: BTreeMap<String, String>,
+    clear_env: Option<bool>,
+    concurrency: Option<Concurrency>,
+    timeout_ms: Option<u64>,
+    stdout: Option<String>,
+}
+
+// checks [defaults] on its own so a mistake in it is reported once instead
+// of for every action it ends up in
+pub fn defaults_from_config(value: Value) -> Result<Table, EntryError> {
+    let defaults: Defaults = deserialize_entry(value)?;
+    for (key, path) in [("shell", &defaults.shell), ("stdout", &defaults.stdout)] {
+        if let Some(path) = path {
+            path_from_config(key, path)?;
+        }
+    }
+    if let Some(cwd) = &defaults.cwd {
+        cwd_from_config(cwd)?;
+    }
+    env_from_config(defaults.env.clone())?;
+
+    Ok(Table::try_from(&defaults).expect("defaults are a table"))
+}
+
+// fills in whatever a `cmd` action or the probe of an `if` leaves out from
+// [defaults], including the ones nested in other actions. `env` is merged
+// with the entries of the action winning.
+pub fn apply_defaults(value: &mut Value, defaults: &Table) {
+    let Value::Table(action) = value else {
+        return;
+    };
+    let keys: &[&str] = match action.get("type").and_then(Value::as_str) {
+        Some("cmd") => &[
+            "shell",
+            "cwd",
+            "env",
+            "clear_env",
+            "concurrency",
+            "timeout_ms",
+            "stdout",
+        ],
+        // a probe is always waited for and its output is only logged
+        Some("if") => &["shell", "cwd", "env", "clear_env", "timeout_ms"],
+        Some("sequence") => {
+            if let Some(Value::Array(actions)) = action.get_mut("actions") {
+                for action in actions.iter_mut() {
+                    apply_defaults(action, defaults);
+                }
+            }
+            return;
+        }
+        _ => return,
+    };
+
+    for branch in ["then", "else"] {
+        if let Some(branch) = action.get_mut(branch) {
+            apply_defaults(branch, defaults);
+        }
+    }
+    for (key, default) in defaults.iter() {
+        // `args` run without a shell
+        if !keys.contains(&key.as_str()) || key == "shell" && action.contains_key("args") {
+            continue;
+        }
+        match (action.get_mut(key), default) {
+            (None, _) => {
+                action.insert(key.clone(), default.clone());
+            }
+            (Some(Value::Table(env)), Value::Table(default_env)) => {
+                for (name, value) in default_env.iter() {
+                    env.entry(name.clone()).or_insert_with(|| value.clone());
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// replaces every reference to an entry of [actions] in `value`, including
+// the ones in nested actions, with the action it names. the names that were
+// referenced end up in `used`.
+pub fn resolve_references(
+    value: &mut Value,
+    named: &Table,
+    used: &mut HashSet<String>,
+) -> Result<(), EntryError> {
+    resolve(value, named, used, &mut Vec::new())
+}
+
+// `stack` holds the named actions that are being resolved, a name that is
+// already on it would never stop expanding. it can't grow past `MAX_DEPTH`
+// either, an action that refers to another one twice on every level would
+// expand into more copies than anyone could want.
+fn resolve(
+    value: &mut Value,
+    named: &Table,
+    used: &mut HashSet<String>,
+    stack: &mut Vec<String>,
+) -> Result<(), EntryError> {
+    let Value::Table(table) = value else {
+        return Ok(());
+    };
+
+    if let Some(reference) = table.remove("action") {
+        // errors about the reference itself point at `action`
+        let at_action = |message: String| EntryError::at("action", message);
+        let Value::String(name) = reference else {
+            return Err(at_action(
+                "`action` has to be the name of an entry in [actions]".to_string(),
+            ));
+        };
+        let Some(action) = named.get(&name) else {
+            return Err(at_action(format!("unknown action `{}`", name)));
+        };
+        if stack.contains(&name) {
+            stack.push(name);
+            return Err(at_action(format!(
+                "actions refer to each other: {}",
+                stack.join(" -> ")
+            )));
+        }
+        used.insert(name.clone());
+
+        let args = match table.remove("args") {
+            None => Table::new(),
+            Some(Value::Table(args)) => args,
+            Some(_) => {
+                return Err(EntryError::at(
+                    "args",
+                    "`args` has to be a table such as `{ dir = \"left\" }`".to_string(),
+                ))
+            }
+        };
+        let at_arg = |arg: &str, message: String| {
+            EntryError::at(arg, message).within(PathPart::Key("args".to_string()))
+        };
+        let mut missing = BTreeSet::new();
+        placeholders(action, &mut missing);
+        missing.retain(|placeholder| !args.contains_key(placeholder));
+        if !missing.is_empty() {
+            let missing: Vec<String> = missing
+                .iter()
+                .map(|placeholder| format!("`{}`", placeholder))
+                .collect();
+            let message = format!("action `{}` needs {} in `args`", name, missing.join(", "));
+            return Err(if args.is_empty() {
+                at_action(message)
+            } else {
+                EntryError::at("args", message)
+            });
+        }
+        let mut action = action.clone();
+        for (arg, value) in args.iter() {
+            let value = match value {
+                Value::String(value) => value.clone(),
+                Value::Integer(_) | Value::Float(_) | Value::Boolean(_) => value.to_string(),
+                _ => {
+                    return Err(at_arg(
+                        arg,
+                        format!("`{}` has to be a string or a number", arg),
+                    ))
+                }
+            };
+            if !substitute(&mut action, arg, &value) {
+                return Err(at_arg(
+                    arg,
+                    format!("action `{}` has no `{{{}}}`", name, arg),
+                ));
+            }
+        }
+
+        // whatever is wrong in the named action is only known by its name
+        // here
+        stack.push(name.clone());
+        if stack.len() > MAX_DEPTH {
+            return Err(at_action(format!(
+                "actions refer to each other more than {} levels deep: {}",
+                MAX_DEPTH,
+                stack.join(" -> ")
+            )));
+        }
+        resolve(&mut action, named, used, stack).map_err(|error| at_action(error.message))?;
+        stack.pop();
+
+        // whatever else is next to the reference (`only_in`, `block`, etc)
+        // stays where it is
+        let Value::Table(action) = action else {
+            return Err(at_action(format!("action `{}` has to be a table", name)));
+        };
+        for (key, value) in action {
+            if table.contains_key(&key) {
+                return Err(EntryError::at(
+                    &key,
+                    format!("`{}` is already set by action `{}`", key, name),
+                ));
+            }
+            table.insert(key, value);
+        }
+        return Ok(());
+    }
+
+    if let Some(Value::Array(actions)) = table.get_mut("actions") {
+        for (i, action) in actions.iter_mut().enumerate() {
+            resolve(action, named, used, stack).map_err(|error| {
+                error
+                    .within(PathPart::Index(i))
+                    .within(PathPart::Key("actions".to_string()))
+            })?;
+        }
+    }
+    for branch in ["then", "else"] {
+        if let Some(action) = table.get_mut(branch) {
+            resolve(action, named, used, stack)
+                .map_err(|error| error.within(PathPart::Key(branch.to_string())))?;
+        }
+    }
+    Ok(())
+}
+
+// the names of the `{placeholders}` in every string in `value`
+fn placeholders(value: &Value, found: &mut BTreeSet<String>) {
+    match value {
+        Value::String(string) => {
+            for (_, name) in find_placeholders(string) {
+                found.insert(name.to_string());
+            }
+        }
+        Value::Array(values) => {
+            for value in values {
+                placeholders(value, found);
+            }
+        }
+        Value::Table(table) => {
+            for (_, value) in table {
+                placeholders(value, found);
+            }
+        }
+        _ => {}
+    }
+}
+
+// the `{placeholders}` in `string` by where they are and their name.
+// `${HOME}` and the like are left to the shell.
+fn find_placeholders(string: &str) -> Vec<(Range<usize>, &str)> {
+    let mut found = Vec::new();
+    let mut from = 0;
+    while let Some(start) = string[from..].find('{').map(|i| from + i) {
+        let after = &string[start + 1..];
+        let len = after
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-'))
+            .unwrap_or(after.len());
+        let shell = string[..start].ends_with('$');
+        if !shell && len > 0 && after[len..].starts_with('}') {
+            found.push((start..start + len + 2, &after[..len]));
+        }
+        from = start + 1;
+    }
+    found
+}
+
+// replaces the placeholder `{name}` in every string in `value`. returns
+// whether there was one.
+fn substitute(value: &mut Value, name: &str, replacement: &str) -> bool {
+    match value {
+        Value::String(string) => {
+            let spans: Vec<Range<usize>> = find_placeholders(string)
+                .into_iter()
+                .filter(|(_, found)| *found == name)
+                .map(|(span, _)| span)
+                .collect();
+            // from the back so the spans before it stay where they are
+            for span in spans.iter().rev() {
+                string.replace_range(span.clone(), replacement);
+            }
+            !spans.is_empty()
+        }
+        // every string has to be replaced so this can't stop at the first
+        Value::Array(values) => {
+            let mut found = false;
+            for value in values.iter_mut() {
+                found |= substitute(value, name, replacement);
+            }
+            found
+        }
+        Value::Table(table) => {
+            let mut found = false;
+            for (_, value) in table.iter_mut() {
+                found |= substitute(value, name, replacement);
+            }
+            found
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, sync::Mutex, time::Duration};
+
+    use rdev::EventType;
+
+    use super::{Action, RunContext};
+    use crate::{
+        executor::{Job, Runner},
+        simulate::EventSink,
+    };
+
+    // records every step instead of running anything. probes exit with
+    // whatever `exit_codes` says for their command.
+    #[derive(Default)]
+    struct FakeExecutor {
+        steps: Mutex<Vec<String>>,
+        exit_codes: HashMap<String, i32>,
+    }
+
+    impl FakeExecutor {
+        fn step(&self, step: String) {
+            self.steps.lock().unwrap().push(step);
+        }
+    }
+
+    impl Runner for FakeExecutor {
+        fn run(&self, job: Job) {
+            self.step(format!("run {}", job.command()));
+        }
+
+        fn probe(&self, job: Job) -> Option<i32> {
+            self.step(format!("probe {}", job.command()));
+            self.exit_codes.get(&job.command()).copied()
+        }
+
+        fn sleep(&self, duration: Duration) {
+            self.step(format!("sleep {}ms", duration.as_millis()));
+        }
+    }
+
+    impl EventSink for FakeExecutor {
+        fn send(&self, event_type: EventType) {
+            if let EventType::KeyPress(key) = event_type {
+                self.step(format!("press {:?}", key));
+            }
+        }
+    }
+
+    fn execute(action: &Action, executor: &FakeExecutor) -> Vec<String> {
+        let set_mode = |name: &str| executor.step(format!("mode {}", name));
+        action.execute(&RunContext {
+            bind: "test",
+            envs: &[],
+            sink: executor,
+            runner: executor,
+            set_mode: &set_mode,
+        });
+        executor.steps.lock().unwrap().drain(..).collect()
+    }
+
+    #[test]
+    fn composite_actions_run_in_order() {
+        let action = Action::from_config(
+            toml::from_str(
+                r#"
+            type = "sequence"
+
+            [[actions]]
+            type = "keys"
+            keys = ["a"]
+
+            [[actions]]
+            type = "sleep"
+            duration_ms = 200
+
+            [[actions]]
+            type = "if"
+            command = "pgrep x"
+            then = { type = "cmd", args = ["yes"] }
+
+            [actions.else]
+            type = "sequence"
+            actions = [{ type = "mode", name = "launch" }, { type = "cmd", command = "no" }]
+            "#,
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert!(action.waits());
+        assert_eq!(action.modes(), vec!["launch"]);
+
+        let mut executor = FakeExecutor::default();
+        assert_eq!(
+            execute(&action, &executor),
+            vec![
+                "press KeyA",
+                "sleep 200ms",
+                "probe pgrep x",
+                "mode launch",
+                "run no"
+            ]
+        );
+
+        executor.exit_codes.insert("pgrep x".to_string(), 0);
+        assert_eq!(
+            execute(&action, &executor),
+            vec!["press KeyA", "sleep 200ms", "probe pgrep x", "run yes"]
+        );
+    }
+
+    #[test]
+    fn nesting_is_limited() {
+        let nested = |depth: usize| {
+            let mut action = r#"{ type = "keys", keys = ["a"] }"#.to_string();
+            for _ in 1..depth {
+                action = format!(r#"{{ type = "sequence", actions = [{}] }}"#, action);
+            }
+            let table: toml::Table = toml::from_str(&format!("action = {}", action)).unwrap();
+            Action::from_config(table["action"].clone())
+        };
+        assert!(nested(8).is_ok());
+        assert_eq!(
+            nested(9).unwrap_err().message,
+            "actions can't be nested more than 8 levels deep"
+        );
+    }
+}
@@ -0,0 +1,180 @@
+use serde::Deserialize;
+
+// tells us which app is focused so binds can be limited to certain apps
+pub trait FocusedAppProvider: Send + Sync {
+    // the names the focused app goes by (on x11 the instance and class names
+    // from WM_CLASS). empty if it can't be figured out.
+    fn focused_app(&self) -> Vec<String>;
+}
+
+// `only_in`/`except_in` on a bind. app names are compared case insensitively.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AppFilter {
+    #[serde(default)]
+    only_in: Vec<String>,
+    #[serde(default)]
+    except_in: Vec<String>,
+}
+
+impl AppFilter {
+    // asking the os for the focused app isn't free so only do it for binds
+    // that actually care
+    pub fn is_empty(&self) -> bool {
+        self.only_in.is_empty() && self.except_in.is_empty()
+    }
+
+    pub fn allows(&self, provider: &dyn FocusedAppProvider) -> bool {
+        if self.is_empty() {
+            return true;
+        }
+
+        let names: Vec<String> = provider
+            .focused_app()
+            .iter()
+            .map(|name| name.to_lowercase())
+            .collect();
+        let matches =
+            |apps: &Vec<String>| apps.iter().any(|app| names.contains(&app.to_lowercase()));
+
+        if !self.only_in.is_empty() && !matches(&self.only_in) {
+            return false;
+        }
+        !matches(&self.except_in)
+    }
+}
+
+// used where we don't know how to find the focused app. `only_in` binds never
+// fire and `except_in` binds always do.
+#[cfg(not(target_os = "linux"))]
+pub struct NoFocusedApp;
+
+#[cfg(not(target_os = "linux"))]
+impl FocusedAppProvider for NoFocusedApp {
+    fn focused_app(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub use linux::X11FocusedApp;
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::{
+        ffi::{CStr, CString},
+        ptr,
+        sync::Mutex,
+    };
+
+    use x11::xlib;
+
+    use super::FocusedAppProvider;
+
+    struct Display(*mut xlib::Display);
+
+    // the display is only ever used while holding the mutex below
+    unsafe impl Send for Display {}
+
+    // reads WM_CLASS of the window in _NET_ACTIVE_WINDOW
+    #[derive(Default)]
+    pub struct X11FocusedApp {
+        display: Mutex<Option<Display>>,
+    }
+
+    impl FocusedAppProvider for X11FocusedApp {
+        fn focused_app(&self) -> Vec<String> {
+            let mut display = self.display.lock().expect("display was poisoned");
+            if display.is_none() {
+                let opened = unsafe { xlib::XOpenDisplay(ptr::null()) };
+                if opened.is_null() {
+                    eprintln!("error opening x11 display to find the focused app");
+                    return Vec::new();
+                }
+                *display = Some(Display(opened));
+            }
+            let display = display.as_ref().expect("display was just opened").0;
+
+            unsafe {
+                let Some(window) = active_window(display) else {
+                    return Vec::new();
+                };
+
+                let mut hint = xlib::XClassHint {
+                    res_name: ptr::null_mut(),
+                    res_class: ptr::null_mut(),
+                };
+                if xlib::XGetClassHint(display, window, &mut hint) == 0 {
+                    return Vec::new();
+                }
+
+                let mut names = Vec::new();
+                for name in [hint.res_name, hint.res_class] {
+                    if !name.is_null() {
+                        names.push(CStr::from_ptr(name).to_string_lossy().into_owned());
+                        xlib::XFree(name.cast());
+                    }
+                }
+                names
+            }
+        }
+    }
+
+    unsafe fn active_window(display: *mut xlib::Display) -> Option<xlib::Window> {
+        let name = CString::new("_NET_ACTIVE_WINDOW").expect("no nul bytes");
+        let atom = xlib::XInternAtom(display, name.as_ptr(), xlib::False);
+        let root = xlib::XDefaultRootWindow(display);
+
+        let mut actual_type = 0;
+        let mut actual_format = 0;
+        let mut items = 0;
+        let mut bytes_after = 0;
+        let mut data: *mut u8 = ptr::null_mut();
+        let status = xlib::XGetWindowProperty(
+            display,
+            root,
+            atom,
+            0,
+            1,
+            xlib::False,
+            xlib::XA_WINDOW,
+            &mut actual_type,
+            &mut actual_format,
+            &mut items,
+            &mut bytes_after,
+            &mut data,
+        );
+        if status != xlib::Success as i32 || data.is_null() {
+            return None;
+        }
+
+        let window = if items == 1 && actual_format == 32 {
+            Some(*(data as *const xlib::Window))
+        } else {
+            None
+        };
+        xlib::XFree(data.cast());
+        window.filter(|window| *window != 0)
+    }
+}
+
+// the focused app for the current platform
+#[cfg(target_os = "linux")]
+pub fn default_provider() -> Box<dyn FocusedAppProvider> {
+    Box::new(X11FocusedApp::default())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn default_provider() -> Box<dyn FocusedAppProvider> {
+    Box::new(NoFocusedApp)
+}
+
+#[cfg(test)]
+pub struct FakeFocusedApp(pub std::sync::Mutex<Vec<String>>);
+
+#[cfg(test)]
+impl FocusedAppProvider for FakeFocusedApp {
+    fn focused_app(&self) -> Vec<String> {
+        self.0.lock().unwrap().clone()
+    }
+}
@@ -0,0 +1,1396 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
+    ops::Range,
+    time::Duration,
+};
+
+use serde::{
+    de::{value, DeserializeOwned, IntoDeserializer},
+    Deserialize,
+};
+use serde_path_to_error::Segment;
+use toml::{Spanned, Table, Value};
+use toml_edit::ImDocument;
+
+use crate::{
+    action::{apply_defaults, defaults_from_config, resolve_references, Action},
+    app::AppFilter,
+    key::{
+        ButtonSet, KeyboardKey, ModifierAlias, ModifierKey, ModifierSet, Modifiers, MouseButton,
+        Trigger,
+    },
+    keymap::Keymap,
+    mouse::MouseMacro,
+    scroll::ScrollMacro,
+};
+
+#[derive(Debug, Eq, PartialEq, Hash, Clone, Copy)]
+pub struct Bind {
+    modifiers: Modifiers,
+    // mouse buttons that are held down like modifiers
+    buttons: ButtonSet,
+    key: Trigger,
+}
+
+// one `+` separated part of a chord
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum KeyName {
+    Modifier(ModifierKey),
+    Alias(ModifierAlias),
+    Keyboard(KeyboardKey),
+    Button(MouseButton),
+}
+
+impl KeyName {
+    fn from_config_str(name: &str) -> Result<Self, String> {
+        let deserializer: value::StrDeserializer<value::Error> = name.into_deserializer();
+        KeyName::deserialize(deserializer).map_err(|_| format!("unknown key `{}`", name))
+    }
+}
+
+impl Bind {
+    pub fn new(modifiers: ModifierSet<ModifierKey>, buttons: ButtonSet, key: Trigger) -> Self {
+        Bind {
+            modifiers: Modifiers {
+                exact: modifiers,
+                aliases: ModifierSet::new(),
+            },
+            buttons,
+            key,
+        }
+    }
+
+    // the modifiers that are named exactly. binds made from what is held
+    // down never have aliases.
+    pub fn modifiers(&self) -> ModifierSet<ModifierKey> {
+        self.modifiers.exact
+    }
+
+    // whether `held`, a chord made from what is held down, is this chord of
+    // the config
+    pub fn matches(&self, held: &Bind) -> bool {
+        self.key == held.key
+            && self.buttons == held.buttons
+            && self.modifiers.matches(held.modifiers.exact)
+    }
+
+    // whether something can be held down that is both chords
+    fn overlaps(&self, other: &Bind) -> bool {
+        self.key == other.key
+            && self.buttons == other.buttons
+            && self.modifiers.overlaps(&other.modifiers)
+    }
+
+    pub fn key(&self) -> Trigger {
+        self.key
+    }
+
+    // parses a single chord such as `meta-left + key-k`. only the first chord
+    // of a sequence has to include a modifier, the rest can be bare keys.
+    //
+    // mouse buttons can be used as the trigger (`control-left + button-middle`)
+    // or held like a modifier (`button-4 + key-k`). if a chord has a keyboard
+    // key that is the trigger, otherwise it is the last button.
+    //
+    // `symbol:/` is whichever key types `/` on the keyboard layout in use,
+    // plus shift if that is needed to type it.
+    fn new_from_config_str(
+        keys: &str,
+        require_modifier: bool,
+        keymap: &dyn Keymap,
+    ) -> Result<Self, String> {
+        let mut modifiers = Modifiers::default();
+        let mut buttons: Vec<MouseButton> = Vec::new();
+        let mut keyboard_key: Option<KeyboardKey> = None;
+        let mut symbol_needs_shift = false;
+        for key in keys.split(" + ") {
+            if let Some(symbol) = key.strip_prefix("symbol:") {
+                let mut chars = symbol.chars();
+                let (Some(symbol), None) = (chars.next(), chars.next()) else {
+                    return Err(format!("`{}` has to be a single character", key));
+                };
+                let Some((k_key, shift)) = keymap.key_for_symbol(symbol) else {
+                    return Err(format!("no key types `{}` on this keyboard layout", symbol));
+                };
+                if keyboard_key.is_some() {
+                    return Err(format!("`{}` has more than one keyboard key", keys));
+                }
+                keyboard_key = Some(k_key);
+                symbol_needs_shift = shift;
+                continue;
+            }
+
+            match KeyName::from_config_str(key)? {
+                KeyName::Modifier(modifier) => {
+                    if !modifiers.exact.insert(modifier) {
+                        return Err(format!("`{}` is in `{}` more than once", key, keys));
+                    }
+                }
+                KeyName::Alias(alias) => {
+                    if !modifiers.aliases.insert(alias) {
+                        return Err(format!("`{}` is in `{}` more than once", key, keys));
+                    }
+                }
+                KeyName::Keyboard(k_key) => {
+                    if !k_key.exists() {
+                        return Err(format!("`{}` doesn't exist on this platform", key));
+                    }
+                    if keyboard_key.is_some() {
+                        return Err(format!("`{}` has more than one keyboard key", keys));
+                    }
+                    keyboard_key = Some(k_key);
+                }
+                KeyName::Button(button) => {
+                    if buttons.contains(&button) {
+                        return Err(format!("`{}` is in `{}` more than once", key, keys));
+                    }
+                    buttons.push(button);
+                }
+            }
+        }
+
+        let key = match keyboard_key {
+            Some(k_key) => Trigger::Keyboard(k_key),
+            None => match buttons.pop() {
+                Some(button) => Trigger::Button(button),
+                None => return Err(format!("`{}` has no keyboard key or mouse button", keys)),
+            },
+        };
+
+        // a shift that was written out already covers it
+        let has_shift = modifiers
+            .exact
+            .iter()
+            .any(|modifier| modifier.alias() == Some(ModifierAlias::Shift));
+        if symbol_needs_shift && !has_shift {
+            modifiers.aliases.insert(ModifierAlias::Shift);
+        }
+
+        if require_modifier && modifiers.is_empty() && buttons.is_empty() {
+            return Err(format!("`{}` has no modifier keys", keys));
+        }
+
+        Ok(Bind {
+            modifiers,
+            buttons: buttons.into_iter().collect(),
+            key,
+        })
+    }
+}
+
+// the bind with its modifiers and buttons in a fixed order, however they were
+// written in the config
+impl fmt::Display for Bind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut names = Vec::new();
+        if !self.modifiers.is_empty() {
+            names.push(self.modifiers.to_string());
+        }
+        names.extend(self.buttons.iter().map(|b| b.to_string()));
+        names.push(self.key.to_string());
+        write!(f, "{}", names.join(" + "))
+    }
+}
+
+// parses the chords of a sequence separated by `,`, e.g. `meta-left + key-k, key-t`
+pub fn sequence_from_config_str(
+    keys: &str,
+    require_modifier: bool,
+    keymap: &dyn Keymap,
+) -> Result<Vec<Bind>, String> {
+    split_chords(keys)
+        .iter()
+        .enumerate()
+        .map(|(i, chord)| {
+            Bind::new_from_config_str(chord.trim(), require_modifier && i == 0, keymap)
+        })
+        .collect()
+}
+
+// splits on every `,` except the one in `symbol:,`
+fn split_chords(keys: &str) -> Vec<&str> {
+    let mut chords = Vec::new();
+    let mut start = 0;
+    for (i, c) in keys.char_indices() {
+        if c == ',' && !keys[..i].ends_with("symbol:") {
+            chords.push(&keys[start..i]);
+            start = i + 1;
+        }
+    }
+    chords.push(&keys[start..]);
+    chords
+}
+
+// parses a set of modifiers on their own such as `shift-left + meta`
+fn modifiers_from_config_str(keys: &str) -> Result<Modifiers, String> {
+    let mut modifiers = Modifiers::default();
+    for key in keys.split(" + ") {
+        let inserted = match KeyName::from_config_str(key)? {
+            KeyName::Modifier(modifier) => modifiers.exact.insert(modifier),
+            KeyName::Alias(alias) => modifiers.aliases.insert(alias),
+            _ => return Err(format!("`{}` is not a modifier key", key)),
+        };
+        if !inserted {
+            return Err(format!("`{}` is in `{}` more than once", key, keys));
+        }
+    }
+    Ok(modifiers)
+}
+
+// one step on the way from an entry of the config to a value inside of it
+#[derive(Debug, Clone, PartialEq)]
+pub enum PathPart {
+    Key(String),
+    Index(usize),
+}
+
+// something that is wrong with a single entry of the config. `path` leads to
+// the key or value that is wrong, it's empty if it's the entry as a whole.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntryError {
+    pub path: Vec<PathPart>,
+    pub message: String,
+}
+
+impl EntryError {
+    // the error is about the value of `key`
+    pub fn at(key: &str, message: String) -> Self {
+        Self {
+            path: vec![PathPart::Key(key.to_string())],
+            message,
+        }
+    }
+
+    // the error is in the value at `part` of whatever reports it
+    pub fn within(mut self, part: PathPart) -> Self {
+        self.path.insert(0, part);
+        self
+    }
+}
+
+impl From<String> for EntryError {
+    fn from(message: String) -> Self {
+        Self {
+            path: Vec::new(),
+            message,
+        }
+    }
+}
+
+// deserializes a single entry of the config on its own so one bad entry
+// doesn't hide the problems with the others. the path of the value that was
+// wrong is kept so the error can point at it.
+pub fn deserialize_entry<T: DeserializeOwned>(value: Value) -> Result<T, EntryError> {
+    serde_path_to_error::deserialize(value).map_err(|err| {
+        let mut path: Vec<PathPart> = err
+            .path()
+            .iter()
+            .filter_map(|segment| match segment {
+                Segment::Seq { index } => Some(PathPart::Index(*index)),
+                Segment::Map { key } => Some(PathPart::Key(key.clone())),
+                // toml has no enums of its own, see `Action::from_config`
+                Segment::Enum { .. } | Segment::Unknown => None,
+            })
+            .collect();
+        let message = err.into_inner().message().to_string();
+        // serde reports an unknown field on the table it is in, the field
+        // itself is easier to find
+        if let Some(field) = message
+            .strip_prefix("unknown field `")
+            .and_then(|rest| rest.split('`').next())
+        {
+            path.push(PathPart::Key(field.to_string()));
+        }
+        EntryError { path, message }
+    })
+}
+
+// moves `keys` out of the table `value` into a table of their own. used for
+// settings that live next to an action in the same table.
+pub fn split_table(value: &mut Value, keys: &[&str]) -> Value {
+    let mut split = Table::new();
+    if let Value::Table(table) = value {
+        for key in keys {
+            if let Some(entry) = table.remove(*key) {
+                split.insert(key.to_string(), entry);
+            }
+        }
+    }
+    Value::Table(split)
+}
+
+// something that is wrong with the config file
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigError {
+    // line and column, both starting at 1
+    pub location: Option<(usize, usize)>,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.location {
+            Some((line, column)) => {
+                write!(f, "line {}, column {}: {}", line, column, self.message)
+            }
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+// collects every error in the config so they can all be fixed in one go
+// instead of one reload at a time
+struct Errors<'a> {
+    content: &'a str,
+    // the file once more but with the span of every key and value, the
+    // entries are parsed from `toml::Value`s which don't have them
+    document: Option<ImDocument<&'a str>>,
+    errors: Vec<ConfigError>,
+}
+
+impl<'a> Errors<'a> {
+    fn location(&self, span: &Range<usize>) -> (usize, usize) {
+        let before = &self.content[..span.start.min(self.content.len())];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        (
+            before.matches('\n').count() + 1,
+            before[line_start..].chars().count() + 1,
+        )
+    }
+
+    fn push(&mut self, span: &Range<usize>, message: String) {
+        self.errors.push(ConfigError {
+            location: Some(self.location(span)),
+            message,
+        });
+    }
+
+    // an error in the entry `entry` (e.g. `["binds", "meta-left + key-q"]`)
+    // which starts at `span`
+    fn push_entry(&mut self, entry: &[&str], span: &Range<usize>, error: EntryError) {
+        let span = self.find(entry, &error.path).unwrap_or(span.clone());
+        self.push(&span, error.message);
+    }
+
+    // the span of the key (or array element) at the end of `path`. values
+    // that were filled in from [defaults] or [actions] aren't in the entry in
+    // the file so the closest thing that is is used.
+    fn find(&self, entry: &[&str], path: &[PathPart]) -> Option<Range<usize>> {
+        let mut item = self.document.as_ref()?.as_item();
+        for key in entry {
+            item = item.get(*key)?;
+        }
+
+        let mut span = None;
+        for part in path {
+            let next = match part {
+                PathPart::Key(key) => item
+                    .as_table_like()
+                    .and_then(|table| table.get_key_value(key))
+                    .map(|(key, value)| (key.span(), value)),
+                PathPart::Index(index) => item.get(*index).map(|value| (value.span(), value)),
+            };
+            let Some((next_span, next)) = next else {
+                break;
+            };
+            span = next_span.or(span);
+            item = next;
+        }
+        span
+    }
+}
+
+// the config file as toml sees it. every entry is kept as a plain value
+// together with where it is in the file and parsed on its own later.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawConfig {
+    settings: Option<Spanned<Value>>,
+    // merged into every `cmd` action
+    defaults: Option<Spanned<Value>>,
+    // actions that are referred to by name with `action = "name"`
+    #[serde(default)]
+    actions: BTreeMap<Spanned<String>, Spanned<Value>>,
+    // a config with only scroll macros doesn't need [binds]
+    #[serde(default)]
+    binds: BTreeMap<Spanned<String>, Spanned<Value>>,
+    #[serde(default)]
+    scroll: BTreeMap<Spanned<String>, Spanned<Value>>,
+    #[serde(default)]
+    mouse: BTreeMap<Spanned<String>, Spanned<Value>>,
+    #[serde(default)]
+    modes: BTreeMap<Spanned<String>, Spanned<RawMode>>,
+}
+
+impl RawConfig {
+    // fills in every reference to [actions]. an entry whose reference can't
+    // be resolved is dropped so it doesn't cause more errors later on.
+    fn resolve_references(&mut self, errors: &mut Errors) {
+        let named: Table = self
+            .actions
+            .iter()
+            .map(|(name, action)| (name.get_ref().clone(), action.get_ref().clone()))
+            .collect();
+        let mut used = HashSet::new();
+
+        let mut resolve =
+            |section: &[&str], table: &mut BTreeMap<Spanned<String>, Spanned<Value>>| {
+                table.retain(|keys, value| {
+                    let span = value.span();
+                    match resolve_references(value.get_mut(), &named, &mut used) {
+                        Ok(()) => true,
+                        Err(error) => {
+                            let entry = [section, &[keys.get_ref().as_str()]].concat();
+                            errors.push_entry(&entry, &span, error);
+                            false
+                        }
+                    }
+                })
+            };
+        resolve(&["binds"], &mut self.binds);
+        resolve(&["scroll"], &mut self.scroll);
+        resolve(&["mouse"], &mut self.mouse);
+        for (name, mode) in self.modes.iter_mut() {
+            resolve(
+                &["modes", name.get_ref(), "binds"],
+                &mut mode.get_mut().binds,
+            );
+        }
+
+        for name in self.actions.keys() {
+            if !used.contains(name.get_ref()) {
+                errors.push(
+                    &name.span(),
+                    format!("action `{}` is never used", name.get_ref()),
+                );
+            }
+        }
+    }
+
+    // every table that can hold an action, scroll transforms are skipped
+    // since they aren't `cmd` actions
+    fn apply_defaults(&mut self, defaults: &Table) {
+        let modes = self
+            .modes
+            .values_mut()
+            .flat_map(|mode| mode.get_mut().binds.values_mut());
+        let actions = self
+            .binds
+            .values_mut()
+            .chain(self.scroll.values_mut())
+            .chain(self.mouse.values_mut())
+            .chain(modes);
+        for action in actions {
+            apply_defaults(action.get_mut(), defaults);
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Settings {
+    sequence_timeout_ms: Option<u64>,
+    pause_bind: Option<String>,
+}
+
+// everything a bind table in the config maps to
+#[derive(Debug)]
+pub struct BindConfig {
+    pub action: Action,
+    pub apps: AppFilter,
+}
+
+impl BindConfig {
+    fn from_config(mut value: Value) -> Result<Self, EntryError> {
+        // the app filter lives in the same table as the action
+        let apps = split_table(&mut value, &["only_in", "except_in"]);
+        Ok(Self {
+            apps: deserialize_entry(apps)?,
+            action: Action::from_config(value)?,
+        })
+    }
+}
+
+// the binds that are active at the same time, either the top level [binds]
+// or the ones of a mode
+#[derive(Debug, Default)]
+pub struct Binds {
+    // a plain bind is just a sequence with a single chord
+    pub macros: HashMap<Vec<Bind>, BindConfig>,
+    // the sequences of `macros` by the key of their first chord. what is
+    // held down can't be looked up in `macros` directly since binds can use
+    // aliases, so it is matched against these instead. no two of them can
+    // match the same keys.
+    by_key: HashMap<Trigger, Vec<Vec<Bind>>>,
+}
+
+impl Binds {
+    // outside of modes the first chord of a bind has to have a modifier so
+    // that normal typing never triggers anything. `mode_names` are the modes
+    // that `mode` actions are allowed to switch to. `section` is where the
+    // table is in the file.
+    fn from_config(
+        table: BTreeMap<Spanned<String>, Spanned<Value>>,
+        section: &[&str],
+        require_modifier: bool,
+        mode_names: &HashSet<String>,
+        keymap: &dyn Keymap,
+        errors: &mut Errors,
+    ) -> Self {
+        // chords separated by `,` form a sequence that has to be typed in
+        // order. we remember where every bind was defined to point at the
+        // first one when a bind shows up twice.
+        let mut macros = HashMap::new();
+        let mut by_key: HashMap<Trigger, Vec<Vec<Bind>>> = HashMap::new();
+        let mut defined_at: HashMap<Vec<Bind>, Range<usize>> = HashMap::new();
+        for (keys, value) in in_file_order(table) {
+            let span = keys.span();
+            let sequence = sequence_from_config_str(keys.get_ref(), require_modifier, keymap);
+            if let Err(message) = &sequence {
+                errors.push(&span, message.clone());
+            }
+            let value_span = value.span();
+            let bind = BindConfig::from_config(value.into_inner());
+            match &bind {
+                Ok(bind) => {
+                    let modes = bind.action.modes();
+                    let unknown = modes
+                        .iter()
+                        .find(|name| **name != DEFAULT_MODE && !mode_names.contains(**name));
+                    if let Some(name) = unknown {
+                        errors.push(&value_span, format!("unknown mode `{}`", name));
+                        continue;
+                    }
+                }
+                Err(error) => {
+                    let entry = [section, &[keys.get_ref().as_str()]].concat();
+                    errors.push_entry(&entry, &value_span, error.clone());
+                }
+            }
+            let (Ok(sequence), Ok(bind)) = (sequence, bind) else {
+                continue;
+            };
+
+            // aliases mean two binds written differently can still fire on
+            // the same keys
+            let earlier = by_key.get(&sequence[0].key).and_then(|sequences| {
+                sequences
+                    .iter()
+                    .find(|earlier| earlier.len() == sequence.len() && overlaps(earlier, &sequence))
+            });
+            if let Some(earlier) = earlier {
+                let (line, _) = errors.location(&defined_at[earlier]);
+                let message = if *earlier == sequence {
+                    format!(
+                        "bind `{}` is already defined on line {}",
+                        keys.get_ref(),
+                        line
+                    )
+                } else {
+                    format!(
+                        "bind `{}` overlaps `{}` on line {}",
+                        keys.get_ref(),
+                        sequence_name(earlier),
+                        line
+                    )
+                };
+                errors.push(&span, message);
+                continue;
+            }
+            defined_at.insert(sequence.clone(), span);
+            by_key
+                .entry(sequence[0].key)
+                .or_default()
+                .push(sequence.clone());
+            macros.insert(sequence, bind);
+        }
+
+        // a bind that is also the start of a sequence would make the sequence
+        // impossible to type, it fires as soon as its keys are held
+        for sequences in by_key.values() {
+            for bind in sequences.iter() {
+                let starts_sequence = sequences.iter().any(|sequence| {
+                    sequence.len() > bind.len() && overlaps(bind, &sequence[..bind.len()])
+                });
+                if starts_sequence {
+                    errors.push(
+                        &defined_at[bind],
+                        "bind is also the start of a sequence".to_string(),
+                    );
+                }
+            }
+        }
+
+        Self { macros, by_key }
+    }
+
+    // the bind `chords`, as they were held down, fire
+    pub fn find(&self, chords: &[Bind]) -> Option<(&[Bind], &BindConfig)> {
+        let sequence = self
+            .candidates(chords)
+            .iter()
+            .find(|sequence| sequence.len() == chords.len() && starts_with(sequence, chords))?;
+        Some((sequence, self.macros.get(sequence)?))
+    }
+
+    // whether `chords`, as they were held down, are the start of a sequence
+    pub fn is_prefix(&self, chords: &[Bind]) -> bool {
+        self.candidates(chords)
+            .iter()
+            .any(|sequence| sequence.len() > chords.len() && starts_with(sequence, chords))
+    }
+
+    // the sequences whose first chord has the same key as `chords`
+    fn candidates(&self, chords: &[Bind]) -> &[Vec<Bind>] {
+        chords
+            .first()
+            .and_then(|chord| self.by_key.get(&chord.key))
+            .map_or(&[], Vec::as_slice)
+    }
+
+    fn names(&self) -> Vec<String> {
+        self.macros
+            .keys()
+            .map(|sequence| sequence_name(sequence))
+            .collect()
+    }
+}
+
+// whether something can be typed that is both sequences, as far as the
+// shorter one goes
+fn overlaps(a: &[Bind], b: &[Bind]) -> bool {
+    a.iter().zip(b).all(|(a, b)| a.overlaps(b))
+}
+
+// whether `sequence` starts with the held down `chords`
+fn starts_with(sequence: &[Bind], chords: &[Bind]) -> bool {
+    sequence.len() >= chords.len()
+        && sequence
+            .iter()
+            .zip(chords)
+            .all(|(bind, held)| bind.matches(held))
+}
+
+// a sequence the way it is written in the config, e.g. `meta-left + comma, key-t`
+pub fn sequence_name(sequence: &[Bind]) -> String {
+    sequence
+        .iter()
+        .map(|bind| bind.to_string())
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+// the name `mode` actions use to go back to the top level binds
+pub const DEFAULT_MODE: &str = "default";
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawMode {
+    #[serde(default)]
+    binds: BTreeMap<Spanned<String>, Spanned<Value>>,
+    #[serde(default)]
+    capture: bool,
+    timeout_ms: Option<u64>,
+    escape: Option<Spanned<String>>,
+}
+
+// a named set of binds that replaces the top level binds while it is active,
+// like the binding modes of i3 or skhd
+#[derive(Debug)]
+pub struct Mode {
+    pub binds: Binds,
+    // swallow keys that aren't bound in the mode instead of letting them
+    // through
+    pub capture: bool,
+    // go back to the default mode after this long without a bind of the mode
+    // being used
+    pub timeout: Option<Duration>,
+    // goes back to the default mode
+    pub escape: Option<Bind>,
+}
+
+impl Mode {
+    fn from_config(
+        name: &str,
+        raw: RawMode,
+        mode_names: &HashSet<String>,
+        keymap: &dyn Keymap,
+        errors: &mut Errors,
+    ) -> Option<Self> {
+        // binds of a mode don't need modifiers, that's the point of having
+        // a mode
+        let section = ["modes", name, "binds"];
+        let binds = Binds::from_config(raw.binds, &section, false, mode_names, keymap, errors);
+
+        let mut escape = None;
+        if let Some(keys) = raw.escape {
+            match Bind::new_from_config_str(keys.get_ref(), false, keymap) {
+                Ok(bind) if binds.macros.contains_key(&vec![bind]) => {
+                    errors.push(
+                        &keys.span(),
+                        format!("`escape` `{}` is also a bind of the mode", keys.get_ref()),
+                    );
+                    return None;
+                }
+                Ok(bind) => escape = Some(bind),
+                Err(message) => {
+                    errors.push(&keys.span(), format!("`escape`: {}", message));
+                    return None;
+                }
+            }
+        }
+
+        Some(Self {
+            binds,
+            capture: raw.capture,
+            timeout: raw.timeout_ms.map(Duration::from_millis),
+            escape,
+        })
+    }
+}
+
+const DEFAULT_SEQUENCE_TIMEOUT: Duration = Duration::from_millis(1000);
+
+#[derive(Debug)]
+pub struct Config {
+    // the binds that are active when no mode is
+    pub binds: Binds,
+    pub modes: HashMap<String, Mode>,
+    // by the set of modifiers that has to be held while scrolling, at most
+    // one of them matches what is held
+    pub scroll: Vec<(Modifiers, ScrollMacro)>,
+    // same as `scroll` but for moving the mouse
+    pub mouse: Vec<(Modifiers, MouseMacro)>,
+    pub sequence_timeout: Duration,
+    // toggles passing every event through untouched
+    pub pause_bind: Option<Bind>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Config {
+    pub fn new() -> Self {
+        Config {
+            binds: Binds::default(),
+            modes: HashMap::new(),
+            scroll: Vec::new(),
+            mouse: Vec::new(),
+            sequence_timeout: DEFAULT_SEQUENCE_TIMEOUT,
+            pause_bind: None,
+        }
+    }
+
+    pub fn new_from_str(content: &str, keymap: &dyn Keymap) -> Result<Self, Vec<ConfigError>> {
+        let mut errors = Errors {
+            content,
+            document: ImDocument::parse(content).ok(),
+            errors: Vec::new(),
+        };
+
+        let mut raw: RawConfig = match toml::from_str(content) {
+            Ok(raw) => raw,
+            Err(err) => {
+                return Err(vec![ConfigError {
+                    location: err.span().map(|span| errors.location(&span)),
+                    message: err.message().to_string(),
+                }])
+            }
+        };
+
+        let mut sequence_timeout = DEFAULT_SEQUENCE_TIMEOUT;
+        let mut pause_bind = None;
+        let settings_span = raw.settings.as_ref().map(|settings| settings.span());
+        if let Some(raw_settings) = raw.settings.take() {
+            let span = raw_settings.span();
+            match deserialize_entry::<Settings>(raw_settings.into_inner()) {
+                Ok(settings) => {
+                    match settings.sequence_timeout_ms {
+                        Some(0) => errors.push_entry(
+                            &["settings"],
+                            &span,
+                            EntryError::at(
+                                "sequence_timeout_ms",
+                                "`sequence_timeout_ms` must be greater than 0".to_string(),
+                            ),
+                        ),
+                        Some(ms) => sequence_timeout = Duration::from_millis(ms),
+                        None => {}
+                    }
+                    if let Some(keys) = settings.pause_bind {
+                        match Bind::new_from_config_str(&keys, true, keymap) {
+                            Ok(bind) => pause_bind = Some(bind),
+                            Err(message) => errors.push_entry(
+                                &["settings"],
+                                &span,
+                                EntryError::at("pause_bind", format!("`pause_bind`: {}", message)),
+                            ),
+                        }
+                    }
+                }
+                Err(error) => errors.push_entry(&["settings"], &span, error),
+            }
+        }
+
+        // references are filled in first so the defaults end up in the
+        // actions they refer to as well
+        raw.resolve_references(&mut errors);
+        if let Some(raw_defaults) = raw.defaults.take() {
+            let span = raw_defaults.span();
+            match defaults_from_config(raw_defaults.into_inner()) {
+                Ok(defaults) => raw.apply_defaults(&defaults),
+                Err(error) => errors.push_entry(
+                    &["defaults"],
+                    &span,
+                    EntryError {
+                        message: format!("`defaults`: {}", error.message),
+                        ..error
+                    },
+                ),
+            }
+        }
+
+        let mode_names: HashSet<String> = raw
+            .modes
+            .keys()
+            .map(|name| name.get_ref().clone())
+            .collect();
+        let binds = Binds::from_config(
+            raw.binds,
+            &["binds"],
+            true,
+            &mode_names,
+            keymap,
+            &mut errors,
+        );
+
+        let mut modes = HashMap::new();
+        for (name, raw_mode) in raw.modes {
+            if name.get_ref() == DEFAULT_MODE {
+                errors.push(
+                    &name.span(),
+                    format!("`{}` is the mode of the top level binds", DEFAULT_MODE),
+                );
+                continue;
+            }
+            if let Some(mode) = Mode::from_config(
+                name.get_ref(),
+                raw_mode.into_inner(),
+                &mode_names,
+                keymap,
+                &mut errors,
+            ) {
+                modes.insert(name.into_inner(), mode);
+            }
+        }
+
+        // the pause bind is checked before any other bind so one that uses
+        // the same keys would never run
+        if let (Some(bind), Some(span)) = (&pause_bind, &settings_span) {
+            let used = binds
+                .by_key
+                .get(&bind.key)
+                .is_some_and(|sequences| sequences.iter().any(|s| s[0].overlaps(bind)));
+            if used {
+                errors.push(
+                    span,
+                    format!("`pause_bind` `{}` is also used by a bind", bind),
+                );
+            }
+        }
+
+        let scroll = modifier_macros_from_config(
+            raw.scroll,
+            "scroll",
+            ScrollMacro::from_config,
+            &mut errors,
+        );
+        let mouse =
+            modifier_macros_from_config(raw.mouse, "mouse", MouseMacro::from_config, &mut errors);
+
+        if !errors.errors.is_empty() {
+            errors
+                .errors
+                .sort_by_key(|error| error.location.unwrap_or_default());
+            return Err(errors.errors);
+        }
+
+        Ok(Config {
+            binds,
+            modes,
+            scroll,
+            mouse,
+            sequence_timeout,
+            pause_bind,
+        })
+    }
+
+    // every bind with its chords joined by `,`, sorted. binds of a mode
+    // start with the name of the mode.
+    pub fn bind_names(&self) -> Vec<String> {
+        let mut names = self.binds.names();
+        for (name, mode) in self.modes.iter() {
+            names.extend(
+                mode.binds
+                    .names()
+                    .iter()
+                    .map(|bind| format!("{}: {}", name, bind)),
+            );
+        }
+        names.sort();
+        names
+    }
+}
+
+// the entries of a table in the order they were written in. errors about
+// duplicates should point at whatever came second.
+fn in_file_order(
+    table: BTreeMap<Spanned<String>, Spanned<Value>>,
+) -> Vec<(Spanned<String>, Spanned<Value>)> {
+    let mut entries: Vec<_> = table.into_iter().collect();
+    entries.sort_by_key(|(keys, _)| keys.span().start);
+    entries
+}
+
+// parses a table such as [scroll] whose keys are sets of modifiers
+fn modifier_macros_from_config<T>(
+    table: BTreeMap<Spanned<String>, Spanned<Value>>,
+    section: &str,
+    from_config: fn(Value) -> Result<T, EntryError>,
+    errors: &mut Errors,
+) -> Vec<(Modifiers, T)> {
+    let mut macros: Vec<(Modifiers, T)> = Vec::new();
+    for (keys, value) in in_file_order(table) {
+        let span = keys.span();
+        let modifiers = modifiers_from_config_str(keys.get_ref());
+        if let Err(message) = &modifiers {
+            errors.push(&span, message.clone());
+        }
+        let value_span = value.span();
+        let parsed = from_config(value.into_inner());
+        if let Err(error) = &parsed {
+            errors.push_entry(&[section, keys.get_ref()], &value_span, error.clone());
+        }
+        let (Ok(modifiers), Ok(parsed)) = (modifiers, parsed) else {
+            continue;
+        };
+
+        let earlier = macros
+            .iter()
+            .find(|(earlier, _)| earlier.overlaps(&modifiers));
+        if let Some((earlier, _)) = earlier {
+            let message = if *earlier == modifiers {
+                format!(
+                    "`{}` is the same set of modifiers as an earlier entry",
+                    keys.get_ref()
+                )
+            } else {
+                format!("`{}` overlaps the earlier `{}`", keys.get_ref(), earlier)
+            };
+            errors.push(&span, message);
+            continue;
+        }
+        macros.push((modifiers, parsed));
+    }
+    macros
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        executor::{Concurrency, Program},
+        keymap::UsKeymap,
+    };
+
+    fn errors(content: &str) -> Vec<String> {
+        Config::new_from_str(content, &UsKeymap)
+            .expect_err("config should not parse")
+            .iter()
+            .map(|error| error.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn all_errors_are_reported_with_their_location() {
+        let content = r#"
+[binds."meta-left + key-q"]
+type = "cmd"
+command = "true"
+
+[binds."meta-left + key-nope"]
+type = "cmd"
+command = "true"
+
+[binds."meta-left + key-w"]
+type = "cmd"
+comand = "true"
+
+[scroll."key-a"]
+multiply = 2
+"#;
+        assert_eq!(
+            errors(content),
+            vec![
+                "line 6, column 8: unknown key `key-nope`",
+                "line 12, column 1: unknown field `comand`, expected one of `command`, `args`, `shell`, `cwd`, `env`, `clear_env`, `concurrency`, `timeout_ms`, `stdout`",
+                "line 14, column 9: `key-a` is not a modifier key",
+            ]
+        );
+    }
+
+    #[test]
+    fn nested_errors_point_at_the_field() {
+        let content = r#"
+[binds."meta-left + key-q"]
+type = "sequence"
+actions = [{ type = "keys", keys = ["a"] }, { type = "cmd", command = "x", timeout_ms = "soon" }]
+
+[binds."meta-left + key-w"]
+type = "if"
+command = "true"
+else = { type = "keys", keys = ["ä"] }
+
+[binds."meta-left + key-e"]
+type = "cmd"
+command = "true"
+env = { "A=B" = "c" }
+
+[scroll."meta-left"]
+type = "launch"
+"#;
+        assert_eq!(
+            errors(content),
+            vec![
+                "line 4, column 76: invalid type: string \"soon\", expected u64",
+                "line 9, column 33: don't know how to type 'ä'",
+                "line 14, column 9: `A=B` can't be the name of an environment variable",
+                "line 17, column 1: unknown variant `launch`, expected one of `cmd`, `keys`, `mode`, `sequence`, `sleep`, `if`",
+            ]
+        );
+    }
+
+    #[test]
+    fn binds_that_overlap_through_aliases_are_reported() {
+        let content = r#"
+[binds."shift + key-k"]
+type = "cmd"
+command = "true"
+
+[binds."shift-left + key-k, key-t"]
+type = "cmd"
+command = "true"
+
+[binds."shift + key-a"]
+type = "cmd"
+command = "true"
+
+[binds."shift-left + key-a"]
+type = "cmd"
+command = "true"
+
+[binds."shift-right + key-a"]
+type = "cmd"
+command = "true"
+
+[scroll.meta]
+multiply = 2
+
+[scroll.meta-right]
+multiply = 3
+"#;
+        assert_eq!(
+            errors(content),
+            vec![
+                "line 2, column 8: bind is also the start of a sequence",
+                "line 14, column 8: bind `shift-left + key-a` overlaps `shift + key-a` on line 10",
+                "line 18, column 8: bind `shift-right + key-a` overlaps `shift + key-a` on line 10",
+                "line 25, column 9: `meta-right` overlaps the earlier `meta`",
+            ]
+        );
+    }
+
+    #[test]
+    fn duplicate_binds_point_at_the_first_definition() {
+        let content = r#"
+[binds."meta-left + shift-left + key-q"]
+type = "cmd"
+command = "true"
+
+[binds."shift-left + meta-left + key-q"]
+type = "cmd"
+command = "true"
+"#;
+        assert_eq!(
+            errors(content),
+            vec!["line 6, column 8: bind `shift-left + meta-left + key-q` is already defined on line 2"]
+        );
+    }
+
+    #[test]
+    fn symbols_are_resolved_through_the_keymap() {
+        let content = r#"
+[binds."ctrl + symbol:?"]
+type = "keys"
+keys = ["x"]
+
+[binds."meta-left + symbol:,, key-t"]
+type = "keys"
+keys = ["x"]
+"#;
+        let config = Config::new_from_str(content, &UsKeymap).unwrap();
+        assert_eq!(
+            config.bind_names(),
+            vec!["meta-left + comma, key-t", "shift + ctrl + slash"]
+        );
+    }
+
+    #[test]
+    fn defaults_are_merged_into_cmd_actions() {
+        let content = r#"
+[defaults]
+shell = "bash"
+cwd = "~"
+env = { A = "1", B = "2" }
+clear_env = true
+concurrency = "queue"
+timeout_ms = 100
+
+[binds."meta-left + key-q"]
+type = "cmd"
+command = "true"
+env = { B = "3" }
+
+[binds."meta-left + key-w"]
+type = "cmd"
+args = ["echo", "hi"]
+timeout_ms = 200
+
+[binds."meta-left + key-e"]
+type = "keys"
+keys = ["x"]
+"#;
+        let config = Config::new_from_str(content, &UsKeymap).unwrap();
+        let action = |keys: &str| {
+            let sequence = sequence_from_config_str(keys, true, &UsKeymap).unwrap();
+            let Action::Cmd {
+                process,
+                concurrency,
+                timeout,
+                ..
+            } = &config.binds.macros[&sequence].action
+            else {
+                panic!("`{}` isn't a cmd action", keys);
+            };
+            (process.clone(), *concurrency, *timeout)
+        };
+
+        let home = std::env::var("HOME").unwrap();
+        let (process, concurrency, timeout) = action("meta-left + key-q");
+        assert_eq!(
+            process.program,
+            Program::Shell {
+                shell: "bash".to_string(),
+                command: "true".to_string()
+            }
+        );
+        assert_eq!(process.cwd, Some(home.into()));
+        assert_eq!(
+            process.env,
+            vec![
+                ("A".to_string(), "1".to_string()),
+                ("B".to_string(), "3".to_string())
+            ]
+        );
+        assert!(process.clear_env);
+        assert_eq!(concurrency, Concurrency::Queue);
+        assert_eq!(timeout, Some(Duration::from_millis(100)));
+
+        let (process, _, timeout) = action("meta-left + key-w");
+        assert_eq!(
+            process.program,
+            Program::Exec(vec!["echo".to_string(), "hi".to_string()])
+        );
+        assert_eq!(timeout, Some(Duration::from_millis(200)));
+    }
+
+    #[test]
+    fn cmd_actions_are_checked_when_loading() {
+        let content = r#"
+[defaults]
+cwd = "/hotkeyd/does/not/exist"
+
+[binds."meta-left + key-q"]
+type = "cmd"
+command = "true"
+args = ["true"]
+
+[binds."meta-left + key-w"]
+type = "cmd"
+shell = "$HOTKEYD_NOT_SET/bash"
+command = "true"
+
+[binds."meta-left + key-e"]
+type = "cmd"
+args = []
+"#;
+        assert_eq!(
+            errors(content),
+            vec![
+                "line 3, column 1: `defaults`: `cwd` `/hotkeyd/does/not/exist` is not a directory",
+                "line 5, column 1: `command` and `args` can't be used together",
+                "line 12, column 1: `$HOTKEYD_NOT_SET` in `$HOTKEYD_NOT_SET/bash` is not set",
+                "line 17, column 1: `args` needs at least the program to run",
+            ]
+        );
+    }
+
+    #[test]
+    fn named_actions_are_filled_in() {
+        let content = r#"
+[actions.focus]
+type = "cmd"
+command = "focus {dir} --wrap={wrap}"
+
+[actions.focus-left]
+action = "focus"
+args = { dir = "left", wrap = "{wrap}" }
+
+[binds."meta-left + key-h"]
+action = "focus-left"
+args = { wrap = true }
+only_in = ["kitty"]
+
+[binds."meta-left + key-l"]
+type = "sequence"
+actions = [{ action = "focus", args = { dir = "right", wrap = false } }]
+"#;
+        let config = Config::new_from_str(content, &UsKeymap).unwrap();
+        let action = |keys: &str| {
+            let sequence = sequence_from_config_str(keys, true, &UsKeymap).unwrap();
+            &config.binds.macros[&sequence]
+        };
+
+        let bind = action("meta-left + key-h");
+        let Action::Cmd { process, .. } = &bind.action else {
+            panic!("not a cmd action");
+        };
+        assert_eq!(process.program.to_string(), "focus left --wrap=true");
+        assert!(!bind
+            .apps
+            .allows(&crate::app::FakeFocusedApp(std::sync::Mutex::new(vec![
+                "firefox".to_string()
+            ]))));
+
+        let Action::Sequence { actions } = &action("meta-left + key-l").action else {
+            panic!("not a sequence action");
+        };
+        let [Action::Cmd { process, .. }] = actions.as_slice() else {
+            panic!("not a cmd action");
+        };
+        assert_eq!(process.program.to_string(), "focus right --wrap=false");
+    }
+
+    #[test]
+    fn shell_variables_are_not_placeholders() {
+        let content = r#"
+[actions.echo]
+type = "cmd"
+command = "echo ${dir} {dir}"
+
+[binds."meta-left + key-h"]
+action = "echo"
+args = { dir = "left" }
+"#;
+        let config = Config::new_from_str(content, &UsKeymap).unwrap();
+        let sequence = sequence_from_config_str("meta-left + key-h", true, &UsKeymap).unwrap();
+        let Action::Cmd { process, .. } = &config.binds.macros[&sequence].action else {
+            panic!("not a cmd action");
+        };
+        assert_eq!(process.program.to_string(), "echo ${dir} left");
+
+        let content = r#"
+[actions.echo]
+type = "cmd"
+command = "echo ${dir}"
+
+[binds."meta-left + key-h"]
+action = "echo"
+args = { dir = "left" }
+"#;
+        assert_eq!(
+            errors(content),
+            vec!["line 8, column 10: action `echo` has no `{dir}`"]
+        );
+    }
+
+    #[test]
+    fn bad_references_are_reported() {
+        let content = r#"
+[actions.a]
+action = "b"
+
+[actions.b]
+type = "sequence"
+actions = [{ action = "a" }]
+
+[actions.lonely]
+type = "keys"
+keys = ["x"]
+
+[actions.focus]
+type = "cmd"
+command = "focus {dir}"
+
+[binds."meta-left + key-q"]
+action = "a"
+
+[binds."meta-left + key-w"]
+action = "nope"
+
+[binds."meta-left + key-e"]
+action = "focus"
+args = { dir = "left", side = "left" }
+
+[binds."meta-left + key-r"]
+action = "focus"
+
+[binds."meta-left + key-t"]
+action = "focus"
+args = { side = "left" }
+"#;
+        assert_eq!(
+            errors(content),
+            vec![
+                "line 9, column 10: action `lonely` is never used",
+                "line 18, column 1: actions refer to each other: a -> b -> a",
+                "line 21, column 1: unknown action `nope`",
+                "line 25, column 24: action `focus` has no `{side}`",
+                "line 28, column 1: action `focus` needs `dir` in `args`",
+                "line 32, column 1: action `focus` needs `dir` in `args`",
+            ]
+        );
+    }
+
+    #[test]
+    fn references_stop_at_the_nesting_limit() {
+        let mut content = String::new();
+        for i in 0..8 {
+            content.push_str(&format!("[actions.a{}]\naction = \"a{}\"\n", i, i + 1));
+        }
+        content.push_str("[actions.a8]\ntype = \"keys\"\nkeys = [\"x\"]\n");
+        content.push_str("[binds.\"meta-left + key-q\"]\naction = \"a0\"\n");
+        assert_eq!(
+            errors(&content),
+            vec![
+                "line 21, column 1: actions refer to each other more than 8 levels deep: \
+                 a0 -> a1 -> a2 -> a3 -> a4 -> a5 -> a6 -> a7 -> a8"
+            ]
+        );
+    }
+}
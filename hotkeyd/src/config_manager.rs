@@ -1,222 +1,145 @@
 use std::{
-    collections::{BTreeSet, HashMap, HashSet},
     env::var,
     fs::read_to_string,
     path::Path,
-    process::Command,
-    sync::{Arc, RwLock},
-    time::Duration,
+    sync::{Arc, Mutex, RwLock},
+    time::{Duration, Instant},
 };
 
 use lazy_static::lazy_static;
 use notify::{FsEventWatcher, RecursiveMode};
 use notify_debouncer_mini::{new_debouncer, DebounceEventResult, Debouncer};
-use toml::{map::Map, Table, Value};
+use rdev::EventType;
 
 use crate::{
-    key::{KeyboardKey, ModifierKey},
-    utils::get_user,
+    action::{Action, RunContext},
+    app::{default_provider, FocusedAppProvider},
+    config::{sequence_from_config_str, sequence_name, Bind, Config, Mode, DEFAULT_MODE},
+    executor::{Executor, RunRecord},
+    key::{ModifierKey, ModifierSet},
+    keymap::{default_keymap, Keymap},
+    mouse::MouseTracker,
+    scroll::{ScrollMacro, ScrollTracker},
+    simulate::EventSink,
 };
 
-#[derive(Debug, Clone)]
-enum Action {
-    Cmd { command: String },
-}
-
-impl Action {
-    pub fn execute(&self) {
-        match self {
-            Action::Cmd { command } => {
-                let mut envs = HashMap::new();
-
-                // we want to set the USER env var to the currently logged in user
-                if let Some(user) = get_user() {
-                    envs.insert("USER", user);
-                }
-
-                // im fairly sure its good practice to check $SHELL instead
-                // of blindly using sh but SHELL isnt accessible for some
-                // reason when using `var("SHELL")`
-                let b = Command::new("sh").args(["-c", command]).envs(envs).output();
-                println!("output: {:?}", b);
-            }
-        }
+// runs an action that was triggered while `modifiers` were held down
+fn run_blocked(action: &Action, modifiers: ModifierSet<ModifierKey>, cx: &RunContext) {
+    // we can't block the modifiers of a bind since we only find out that
+    // they are part of one once the keyboard key comes in (see
+    // docs/observations.md). instead we release them for the duration of the
+    // action so they don't leak into it (`:WA` instead of `:wa`) and press
+    // them again once it is done. only typed keys can pick them up so other
+    // actions leave them alone, otherwise scroll and mouse macros that fire
+    // on every tick would make them flicker. `cmd` actions only get started
+    // here anyway, they keep running in the background.
+    if !action.types_keys() {
+        action.execute(cx);
+        return;
     }
 
-    fn new_from_config_map(config_map: &Map<String, Value>) -> Option<Self> {
-        let type_val = match config_map.get("type") {
-            Some(t) => t,
-            None => {
-                eprintln!("error parsing config: action does not have a property `type`");
-                return None;
-            }
-        };
-
-        let type_str = match type_val {
-            Value::String(s) => s,
-            _ => return None,
-        };
-
-        let action = match type_str.as_str() {
-            "cmd" => {
-                let command_val = match config_map.get("command") {
-                    Some(cmd) => cmd,
-                    None => return None,
-                };
+    for modifier in modifiers.iter() {
+        cx.sink.send(EventType::KeyRelease(modifier.to_rdev()));
+    }
 
-                let command_str = match command_val {
-                    Value::String(s) => s,
-                    _ => return None,
-                };
+    action.execute(cx);
 
-                Action::Cmd {
-                    command: command_str.to_string(),
-                }
-            }
-            _ => return None,
-        };
-
-        Some(action)
+    for modifier in modifiers.iter() {
+        cx.sink.send(EventType::KeyPress(modifier.to_rdev()));
     }
 }
 
-#[derive(Debug, Eq, PartialEq, Hash, Clone)]
-struct Bind {
-    modifiers: BTreeSet<ModifierKey>,
-    key: KeyboardKey,
+pub enum Lookup {
+    // the chords matched a bind and its action was run
+    Executed,
+    // the chords are the start of at least one sequence bind
+    Pending,
+    // the active mode swallowed the key, either because it isn't bound and
+    // the mode captures unbound keys or because it is the escape
+    Captured,
+    NoMatch,
 }
 
-#[derive(Debug)]
-struct Config {
-    macros: HashMap<Bind, Action>,
+// the config in `path` and the keyboard layout its `symbol:` binds were
+// resolved with, or every problem with the config if it can't be used. the
+// layout is read again every time so switching it only takes a reload.
+fn load_config(path: &str) -> Result<(Config, Box<dyn Keymap>), Vec<String>> {
+    let content = match read_to_string(path) {
+        Ok(content) => content,
+        Err(error) => {
+            return Err(vec![format!(
+                "error reading config file {}: {}",
+                path, error
+            )])
+        }
+    };
+
+    let keymap = default_keymap();
+    let config = Config::new_from_str(&content, keymap.as_ref()).map_err(|errors| {
+        errors
+            .iter()
+            .map(|error| format!("error parsing config {}: {}", path, error))
+            .collect::<Vec<String>>()
+    })?;
+    Ok((config, keymap))
 }
 
-impl Config {
-    pub fn new() -> Self {
-        let macros = HashMap::new();
-        Config { macros }
-    }
-
-    fn new_from_file(path: String) -> Self {
-        // parse file here
-        //
-        let content = match read_to_string(path) {
-            Ok(content) => content,
-            Err(error) => {
-                eprintln!("error reading config file: {}", error);
-                return Config::new();
-            }
-        };
-
-        //parse that shit
-        let parsed_config = match content.parse::<Table>() {
-            Ok(config) => config,
-            Err(error) => {
-                eprintln!("error parsing config: {}", error);
-                return Config::new();
-            }
-        };
-
-        // check if [binds] was defined in the config
-        let binds_content = match parsed_config.get("binds") {
-            Some(binds_content) => binds_content,
-            None => {
-                eprintln!("error parsing config: [binds] does not exist in config file");
-                // maybe not exit here? maybe its better to just assume no binds were set instead
-                // of failing with an error.
-                return Config::new();
-            }
-        };
-
-        // check that [binds] is a table
-        let binds_table = match binds_content {
-            toml::Value::Table(table) => table,
-            _ => {
-                eprintln!("error parsing config: [binds] exists but it is not a table");
-                return Config::new();
-            }
-        };
-
-        let mut macros = HashMap::new();
-
-        // parse all the binds
-        for (keys, value) in binds_table.clone() {
-            let mut modifiers: BTreeSet<ModifierKey> = BTreeSet::new();
-            let mut keyboard_key: Option<KeyboardKey> = None;
-            for key in keys.split(" + ").collect::<Vec<&str>>() {
-                let modifier_key_conv = ModifierKey::from_config_kebab(key);
-                let keyboard_key_conv = KeyboardKey::from_config_kebab(key);
-                match (modifier_key_conv, keyboard_key_conv) {
-                    (Some(key), None) => {
-                        let ins = modifiers.insert(key);
-                        if !ins {
-                            panic!(
-                                "multiple instances of the same modifier in macro: {:?}",
-                                key
-                            );
-                        }
-                    }
-                    (None, Some(key)) => {
-                        if keyboard_key != None {
-                            panic!("multiple keyboard keys in macro: {:?}", key);
-                        }
-                        keyboard_key = Some(key);
-                    }
-                    _ => panic!("key is both modifier and keyboard key: {}", key),
-                }
-            }
-
-            if keyboard_key.is_none() {}
-
-            if modifiers.len() == 0 {
-                panic!("no modifier keys in macro: {:?}", keys);
-            }
-
-            let bind = {
-                let some_keyboard_key = match keyboard_key {
-                    Some(k_key) => k_key,
-                    None => panic!("no keyboard key in macro: {:?}", keys),
-                };
-
-                Bind {
-                    modifiers,
-                    key: some_keyboard_key,
-                }
-            };
-
-            let value_table = match value {
-                Value::Table(t) => t,
-                _ => panic!("value of bind is not a table: {:?}", value),
-            };
-
-            let action = match Action::new_from_config_map(&value_table) {
-                Some(action) => action,
-                None => {
-                    eprintln!("error parsing config: invalid action");
-                    return Config::new();
-                }
-            };
-
-            if macros.insert(bind.clone(), action).is_some() {
-                panic!("bind already exists: {:?}", bind);
-            }
+// swaps in the config in `path`. a file with mistakes in it shouldn't take
+// down the binds that were working so the running config is kept until the
+// file is fixed.
+fn reload(
+    config: &RwLock<Config>,
+    keymap: &RwLock<Box<dyn Keymap>>,
+    path: &str,
+) -> Result<(), Vec<String>> {
+    let (new_config, new_keymap) = load_config(path).inspect_err(|errors| {
+        for error in errors {
+            eprintln!("{}", error);
         }
+        eprintln!("keeping the previous config");
+    })?;
+    *config.write().expect("poisoned") = new_config;
+    *keymap.write().expect("poisoned") = new_keymap;
+    Ok(())
+}
 
-        return Config { macros };
-    }
+struct ActiveMode {
+    name: String,
+    // when the mode was entered or one of its binds was last used, for the
+    // timeout of the mode
+    last_used: Instant,
 }
 
 pub struct ConfigManager {
     config: Arc<RwLock<Config>>,
-
-    _fs_watcher_handle: Debouncer<FsEventWatcher>,
+    // where the config was read from, none if it didn't come from a file
+    path: Option<String>,
+    focused_app: Box<dyn FocusedAppProvider>,
+    // the layout the config was loaded with, for `symbol:` binds
+    keymap: Arc<RwLock<Box<dyn Keymap>>>,
+    // none while the top level binds are active
+    mode: Mutex<Option<ActiveMode>>,
+    // runs `cmd` actions in the background
+    executor: Executor,
+
+    _fs_watcher_handle: Option<Debouncer<FsEventWatcher>>,
 }
 
 impl ConfigManager {
     fn new(config_file_path: String) -> Self {
-        let config = Arc::new(RwLock::new(Config::new_from_file(config_file_path.clone())));
+        // there is nothing to fall back to on startup so we run without any
+        // binds until the file is fixed
+        let (config, keymap) = load_config(&config_file_path).unwrap_or_else(|errors| {
+            for error in errors {
+                eprintln!("{}", error);
+            }
+            (Config::new(), default_keymap())
+        });
+        let config = Arc::new(RwLock::new(config));
+        let keymap = Arc::new(RwLock::new(keymap));
         let cfg = config.clone();
         let fp = config_file_path.clone();
+        let km = keymap.clone();
         let mut _fs_watcher_handle = new_debouncer(
             Duration::from_secs(1),
             move |events: DebounceEventResult| {
@@ -229,12 +152,9 @@ impl ConfigManager {
                 };
 
                 for event in events {
-                    match event.kind {
-                        notify_debouncer_mini::DebouncedEventKind::Any => {
-                            *config.write().expect("poisoned") = Config::new_from_file(fp.clone());
-                            return;
-                        }
-                        _ => {}
+                    if event.kind == notify_debouncer_mini::DebouncedEventKind::Any {
+                        let _ = reload(&config, &km, &fp);
+                        return;
                     }
                 }
             },
@@ -249,21 +169,291 @@ impl ConfigManager {
         }
         Self {
             config: cfg,
-            _fs_watcher_handle,
+            path: Some(config_file_path),
+            focused_app: default_provider(),
+            keymap,
+            mode: Mutex::new(None),
+            executor: Executor::new(),
+            _fs_watcher_handle: Some(_fs_watcher_handle),
+        }
+    }
+
+    // a manager that serves a fixed config and never reloads it
+    pub fn new_from_str(content: &str, focused_app: Box<dyn FocusedAppProvider>) -> Self {
+        use crate::keymap::UsKeymap;
+
+        Self {
+            config: Arc::new(RwLock::new(
+                Config::new_from_str(content, &UsKeymap).expect("invalid test config"),
+            )),
+            path: None,
+            focused_app,
+            keymap: Arc::new(RwLock::new(Box::new(UsKeymap))),
+            mode: Mutex::new(None),
+            executor: Executor::new(),
+            _fs_watcher_handle: None,
+        }
+    }
+
+    // reads the config file again right away instead of waiting for the
+    // watcher to notice a change
+    pub fn reload(&self) -> Result<(), Vec<String>> {
+        let Some(path) = &self.path else {
+            return Err(vec!["the config wasn't read from a file".to_string()]);
+        };
+        reload(&self.config, &self.keymap, path)
+    }
+
+    pub fn path(&self) -> Option<&str> {
+        self.path.as_deref()
+    }
+
+    pub fn bind_names(&self) -> Vec<String> {
+        self.config.read().expect("poisoned").bind_names()
+    }
+
+    // runs the action of the bind written as `keys` as if it had been typed.
+    // binds of a mode are written `mode: keys`, as `bind_names` lists them.
+    // the app filter and the active mode are ignored since it was asked for
+    // explicitly.
+    pub fn trigger(&'static self, keys: &str, sink: &'static dyn EventSink) -> Result<(), String> {
+        let config = self.config.read().expect("poisoned");
+        let (binds, keys) = match keys.split_once(": ") {
+            Some((name, keys)) => match config.modes.get(name) {
+                Some(mode) => (&mode.binds, keys),
+                None => return Err(format!("no mode `{}`", name)),
+            },
+            None => (&config.binds, keys),
+        };
+        let keymap = self.keymap.read().expect("poisoned");
+        let sequence = sequence_from_config_str(keys, false, keymap.as_ref())?;
+        let Some(bind) = binds.macros.get(&sequence) else {
+            return Err(format!("no bind for `{}`", keys));
+        };
+        let name = sequence_name(&sequence);
+        self.run(&bind.action, &name, ModifierSet::new(), sink, &[]);
+        Ok(())
+    }
+
+    // the most recent `cmd` runs, oldest first
+    pub fn history(&self) -> Vec<RunRecord> {
+        self.executor.history()
+    }
+
+    // the name of the active mode
+    pub fn mode(&self) -> String {
+        let config = self.config.read().expect("poisoned");
+        self.active_mode(&config);
+        match &*self.mode.lock().expect("mode was poisoned") {
+            Some(active) => active.name.clone(),
+            None => DEFAULT_MODE.to_string(),
         }
     }
 
-    pub fn exec(&self, modifiers: &HashSet<ModifierKey>, key: &KeyboardKey) -> Option<()> {
+    // the active mode unless it timed out or disappeared in a reload, in
+    // which case we are back to the top level binds
+    fn active_mode<'a>(&self, config: &'a Config) -> Option<&'a Mode> {
+        let mut active = self.mode.lock().expect("mode was poisoned");
+        let current = active.as_ref()?;
+        let mode = config.modes.get(&current.name).filter(|mode| {
+            mode.timeout
+                .is_none_or(|timeout| current.last_used.elapsed() < timeout)
+        });
+        if mode.is_none() {
+            *active = None;
+            println!("mode: {}", DEFAULT_MODE);
+        }
+        mode
+    }
+
+    fn set_mode(&self, name: &str) {
+        let mut active = self.mode.lock().expect("mode was poisoned");
+        *active = if name == DEFAULT_MODE {
+            None
+        } else {
+            Some(ActiveMode {
+                name: name.to_string(),
+                last_used: Instant::now(),
+            })
+        };
+        println!("mode: {}", name);
+    }
+
+    // runs `action` unless it switches modes, which is done right here
+    fn run(
+        &'static self,
+        action: &Action,
+        bind: &str,
+        modifiers: ModifierSet<ModifierKey>,
+        sink: &'static dyn EventSink,
+        envs: &[(&str, String)],
+    ) {
+        let envs: Vec<(String, String)> = envs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.clone()))
+            .collect();
+        let set_mode = |name: &str| self.set_mode(name);
+
+        match action {
+            Action::Mode { name } => self.set_mode(name),
+            // sleeping or waiting for a probe would hold up every key so the
+            // action runs on its own thread. the modifiers of the bind are
+            // only released, by the time it's done the user has most likely
+            // let go of them and pressing them again would leave them stuck.
+            action if action.waits() => {
+                if action.types_keys() {
+                    for modifier in modifiers.iter() {
+                        sink.send(EventType::KeyRelease(modifier.to_rdev()));
+                    }
+                }
+                let action = action.clone();
+                let bind = bind.to_string();
+                self.executor.spawn_blocking(move || {
+                    action.execute(&RunContext {
+                        bind: &bind,
+                        envs: &envs,
+                        sink,
+                        runner: &self.executor,
+                        set_mode: &set_mode,
+                    });
+                });
+            }
+            action => run_blocked(
+                action,
+                modifiers,
+                &RunContext {
+                    bind,
+                    envs: &envs,
+                    sink,
+                    runner: &self.executor,
+                    set_mode: &set_mode,
+                },
+            ),
+        }
+    }
+
+    pub fn exec(&'static self, chords: &[Bind], sink: &'static dyn EventSink) -> Lookup {
         let config = self.config.read().expect("poisonsed");
-        let b = Bind {
-            modifiers: modifiers.into_iter().map(|v| *v).collect(),
-            key: *key,
+        let mode = self.active_mode(&config);
+        let unbound = match mode {
+            Some(mode) if mode.capture => Lookup::Captured,
+            _ => Lookup::NoMatch,
         };
-        let Some(action) = config.macros.get(&b) else {
-            return None;
+
+        // binds can use aliases such as `shift` so the held keys are matched
+        // against them instead of being looked up as they are
+        if let Some(escape) = mode.and_then(|mode| mode.escape.as_ref()) {
+            if let [chord] = chords {
+                if escape.matches(chord) {
+                    self.set_mode(DEFAULT_MODE);
+                    return Lookup::Captured;
+                }
+            }
+        }
+
+        let binds = mode.map_or(&config.binds, |mode| &mode.binds);
+        if let Some((sequence, bind)) = binds.find(chords) {
+            // the bind doesn't apply to the focused app so the keys go
+            // through as if it didn't exist
+            if !bind.apps.allows(self.focused_app.as_ref()) {
+                return unbound;
+            }
+
+            if let Some(active) = self.mode.lock().expect("mode was poisoned").as_mut() {
+                active.last_used = Instant::now();
+            }
+            let modifiers = chords.last().expect("empty bind").modifiers();
+            self.run(&bind.action, &sequence_name(sequence), modifiers, sink, &[]);
+            return Lookup::Executed;
+        }
+
+        if binds.is_prefix(chords) {
+            return Lookup::Pending;
+        }
+
+        unbound
+    }
+
+    // returns true if the scroll should be blocked
+    pub fn scroll(
+        &'static self,
+        modifiers: ModifierSet<ModifierKey>,
+        delta_x: i64,
+        delta_y: i64,
+        tracker: &mut ScrollTracker,
+        sink: &'static dyn EventSink,
+    ) -> bool {
+        let config = self.config.read().expect("poisoned");
+        let Some((variant, scroll)) = config
+            .scroll
+            .iter()
+            .find(|(variant, _)| variant.matches(modifiers))
+        else {
+            tracker.reset();
+            return false;
+        };
+
+        match scroll {
+            ScrollMacro::Transform(transform) => {
+                if let Some((delta_x, delta_y)) = transform.apply(delta_x, delta_y, tracker) {
+                    sink.send(EventType::Wheel { delta_x, delta_y });
+                }
+            }
+            ScrollMacro::Action(action) => {
+                tracker.reset();
+                let envs = [
+                    ("HOTKEYD_DELTA_X", delta_x.to_string()),
+                    ("HOTKEYD_DELTA_Y", delta_y.to_string()),
+                ];
+                let name = format!("scroll {}", variant);
+                self.run(action, name.trim_end(), modifiers, sink, &envs);
+            }
+        }
+        true
+    }
+
+    // returns true if the mouse move should be blocked
+    pub fn mouse_move(
+        &'static self,
+        modifiers: ModifierSet<ModifierKey>,
+        x: f64,
+        y: f64,
+        tracker: &mut MouseTracker,
+        sink: &'static dyn EventSink,
+    ) -> bool {
+        let config = self.config.read().expect("poisoned");
+        let Some((variant, mouse)) = config
+            .mouse
+            .iter()
+            .find(|(variant, _)| variant.matches(modifiers))
+        else {
+            tracker.moved(x, y, None);
+            return false;
+        };
+
+        if let Some(motion) = tracker.moved(x, y, Some(mouse.rate_limit)) {
+            let name = format!("mouse {}", variant);
+            self.run(
+                &mouse.action,
+                name.trim_end(),
+                modifiers,
+                sink,
+                &motion.envs(),
+            );
+        }
+        mouse.block
+    }
+
+    pub fn is_pause_bind(&self, bind: &Bind) -> bool {
+        let config = self.config.read().expect("poisoned");
+        let Some(pause_bind) = &config.pause_bind else {
+            return false;
         };
+        pause_bind.matches(bind)
+    }
 
-        Some(action.execute())
+    pub fn sequence_timeout(&self) -> Duration {
+        self.config.read().expect("poisoned").sequence_timeout
     }
 }
 
@@ -271,3 +461,38 @@ lazy_static! {
     pub static ref CONFIG_MANAGER: ConfigManager =
         ConfigManager::new(var("HOTKEYD_CONFIG").expect("no config").to_string());
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env::temp_dir,
+        fs::{remove_file, write},
+        process,
+    };
+
+    use super::*;
+
+    #[test]
+    fn invalid_config_keeps_the_previous_one() {
+        let path = temp_dir().join(format!("hotkeyd-reload-{}.toml", process::id()));
+        let path = path.to_str().unwrap();
+        write(
+            path,
+            "[binds.\"meta-left + key-q\"]\ntype = \"cmd\"\ncommand = \"true\"\n",
+        )
+        .unwrap();
+        let (config, keymap) = load_config(path).unwrap();
+        let config = RwLock::new(config);
+        let keymap = RwLock::new(keymap);
+
+        write(path, "[binds.\"meta-left + key-q\"]\ntype = \"cmdd\"\n").unwrap();
+        assert!(reload(&config, &keymap, path).is_err());
+        assert_eq!(config.read().unwrap().binds.macros.len(), 1);
+
+        write(path, "").unwrap();
+        assert!(reload(&config, &keymap, path).is_ok());
+        assert!(config.read().unwrap().binds.macros.is_empty());
+
+        remove_file(path).unwrap();
+    }
+}
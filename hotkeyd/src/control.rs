@@ -0,0 +1,470 @@
+use std::{
+    env::{temp_dir, var},
+    fs::{self, DirBuilder, Permissions},
+    io::{BufRead, BufReader, ErrorKind, Write},
+    os::unix::{
+        fs::{DirBuilderExt, FileTypeExt, MetadataExt, PermissionsExt},
+        net::UnixStream,
+    },
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader as AsyncBufReader},
+    net::{UnixListener, UnixStream as AsyncUnixStream},
+    signal::unix::{signal, SignalKind},
+    task,
+};
+
+use crate::{executor::RunRecord, handler::Handler};
+
+// json-rpc 2.0 error codes
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const SERVER_ERROR: i64 = -32000;
+
+// the socket the daemon listens on. $XDG_RUNTIME_DIR is only accessible by
+// the user. without it the socket goes in a directory of its own in the temp
+// dir, which everyone can write to on linux, so nobody else can put a socket
+// where we look for ours.
+pub fn socket_path() -> Result<PathBuf, String> {
+    match var("XDG_RUNTIME_DIR") {
+        Ok(dir) if !dir.is_empty() => Ok(PathBuf::from(dir).join("hotkeyd.sock")),
+        _ => {
+            // SAFETY: getuid has no memory safety requirements and can't fail
+            let uid = unsafe { libc::getuid() };
+            let dir = temp_dir().join(format!("hotkeyd-{}", uid));
+            private_dir(&dir, uid)?;
+            Ok(dir.join("hotkeyd.sock"))
+        }
+    }
+}
+
+// creates `dir` so only the user `uid` can get into it, or makes sure that
+// is true of the one that is already there
+fn private_dir(dir: &Path, uid: u32) -> Result<(), String> {
+    match DirBuilder::new().mode(0o700).create(dir) {
+        Err(err) if err.kind() != ErrorKind::AlreadyExists => {
+            return Err(format!("error creating {}: {}", dir.display(), err));
+        }
+        _ => {}
+    }
+    let metadata = fs::symlink_metadata(dir)
+        .map_err(|err| format!("error reading {}: {}", dir.display(), err))?;
+    if !metadata.is_dir() || metadata.uid() != uid || metadata.mode() & 0o077 != 0 {
+        return Err(format!(
+            "{} has to be a directory that only you can access",
+            dir.display()
+        ));
+    }
+    Ok(())
+}
+
+// one request per line
+#[derive(Deserialize)]
+struct Request {
+    #[serde(default)]
+    id: Value,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Serialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+// one response per line, in the order the requests came in
+#[derive(Serialize)]
+struct Response {
+    jsonrpc: &'static str,
+    id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<RpcError>,
+}
+
+fn dispatch(handler: &Handler, method: &str, params: &Value) -> Result<Value, RpcError> {
+    let config_manager = handler.config_manager();
+    match method {
+        "reload" => config_manager
+            .reload()
+            .map(|()| json!("reloaded"))
+            .map_err(|errors| RpcError::new(SERVER_ERROR, errors.join("\n"))),
+        "list-binds" => Ok(json!(config_manager.bind_names())),
+        "pause" => {
+            handler.pause();
+            Ok(json!({ "paused": true }))
+        }
+        "resume" => {
+            handler.resume();
+            Ok(json!({ "paused": false }))
+        }
+        "trigger" => {
+            let Some(keys) = params.get("bind").and_then(Value::as_str) else {
+                return Err(RpcError::new(
+                    INVALID_PARAMS,
+                    "`trigger` needs a `bind` such as `meta-left + key-k`",
+                ));
+            };
+            handler
+                .trigger(keys)
+                .map(|()| json!("triggered"))
+                .map_err(|message| RpcError::new(SERVER_ERROR, message))
+        }
+        "history" => {
+            let history = config_manager.history();
+            let limit = match params.get("limit") {
+                None => history.len(),
+                Some(limit) => match limit.as_u64() {
+                    Some(limit) => limit as usize,
+                    None => {
+                        return Err(RpcError::new(
+                            INVALID_PARAMS,
+                            "`limit` has to be a positive number",
+                        ))
+                    }
+                },
+            };
+            let skip = history.len().saturating_sub(limit);
+            Ok(json!(history[skip..]))
+        }
+        "status" => Ok(json!({
+            "paused": handler.is_paused(),
+            "mode": config_manager.mode(),
+            "config": config_manager.path(),
+            "binds": config_manager.bind_names().len(),
+        })),
+        _ => Err(RpcError::new(
+            METHOD_NOT_FOUND,
+            format!("unknown method `{}`", method),
+        )),
+    }
+}
+
+// answers a single line of the protocol
+fn handle_line(handler: &Handler, line: &str) -> String {
+    let response = match serde_json::from_str::<Request>(line) {
+        Ok(request) => {
+            let (result, error) = match dispatch(handler, &request.method, &request.params) {
+                Ok(result) => (Some(result), None),
+                Err(error) => (None, Some(error)),
+            };
+            Response {
+                jsonrpc: "2.0",
+                id: request.id,
+                result,
+                error,
+            }
+        }
+        Err(err) => Response {
+            jsonrpc: "2.0",
+            id: Value::Null,
+            result: None,
+            error: Some(RpcError::new(PARSE_ERROR, err.to_string())),
+        },
+    };
+    serde_json::to_string(&response).expect("responses always serialize")
+}
+
+async fn serve_connection(handler: &'static Handler, stream: AsyncUnixStream) {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = AsyncBufReader::new(reader).lines();
+    loop {
+        let line = match lines.next_line().await {
+            Ok(Some(line)) => line,
+            Ok(None) => return,
+            Err(err) => {
+                eprintln!("error reading from control socket: {}", err);
+                return;
+            }
+        };
+
+        // actions can take a while (`cmd` waits for the command to finish)
+        // so they shouldn't hold up the runtime
+        let response = task::spawn_blocking(move || handle_line(handler, &line))
+            .await
+            .expect("control request panicked");
+        if let Err(err) = writer.write_all(format!("{}\n", response).as_bytes()).await {
+            eprintln!("error writing to control socket: {}", err);
+            return;
+        }
+    }
+}
+
+// listens on the control socket until the daemon exits
+pub async fn serve(handler: &'static Handler) {
+    let path = match socket_path() {
+        Ok(path) => path,
+        Err(err) => {
+            eprintln!("{}", err);
+            return;
+        }
+    };
+    // a socket left behind by a daemon that didn't exit cleanly would make
+    // the bind fail. anything else is left alone.
+    match fs::symlink_metadata(&path) {
+        Ok(metadata) if metadata.file_type().is_socket() => {
+            let _ = fs::remove_file(&path);
+        }
+        Ok(_) => {
+            eprintln!("{} is in the way of the socket", path.display());
+            return;
+        }
+        Err(_) => {}
+    }
+    let listener = match UnixListener::bind(&path) {
+        Ok(listener) => listener,
+        Err(err) => {
+            eprintln!("error listening on {}: {}", path.display(), err);
+            return;
+        }
+    };
+    // anyone who can connect can run every bind
+    if let Err(err) = fs::set_permissions(&path, Permissions::from_mode(0o600)) {
+        eprintln!("error restricting {}: {}", path.display(), err);
+        return;
+    }
+
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                tokio::spawn(serve_connection(handler, stream));
+            }
+            Err(err) => eprintln!("error accepting control connection: {}", err),
+        }
+    }
+}
+
+// `kill -USR1` toggles pausing for anything that can't talk to the socket
+pub async fn pause_on_signal(handler: &'static Handler) {
+    let mut signals = match signal(SignalKind::user_defined1()) {
+        Ok(signals) => signals,
+        Err(err) => {
+            eprintln!("error listening for SIGUSR1: {}", err);
+            return;
+        }
+    };
+    while signals.recv().await.is_some() {
+        handler.toggle_pause();
+    }
+}
+
+// sends a single request to the running daemon and returns its result or
+// the error message
+fn request(method: &str, params: Value) -> Result<Value, String> {
+    let path = socket_path()?;
+    let mut stream = UnixStream::connect(&path).map_err(|err| {
+        format!(
+            "error connecting to {} (is the daemon running?): {}",
+            path.display(),
+            err
+        )
+    })?;
+
+    let request = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+    writeln!(stream, "{}", request).map_err(|err| format!("error sending request: {}", err))?;
+
+    let mut line = String::new();
+    BufReader::new(&stream)
+        .read_line(&mut line)
+        .map_err(|err| format!("error reading response: {}", err))?;
+    let mut response: Value = serde_json::from_str(&line)
+        .map_err(|err| format!("error parsing response {:?}: {}", line, err))?;
+
+    if let Some(error) = response.get("error") {
+        let message = error.get("message").and_then(Value::as_str).unwrap_or("");
+        return Err(message.to_string());
+    }
+    Ok(response
+        .get_mut("result")
+        .map(Value::take)
+        .unwrap_or(Value::Null))
+}
+
+// sends `method` to the running daemon and prints what it answered. returns
+// whether the request succeeded.
+pub fn ctl(method: &str, args: &[String]) -> bool {
+    let params = match method {
+        "trigger" => json!({ "bind": args.join(" ") }),
+        _ => Value::Null,
+    };
+    match request(method, params) {
+        Ok(Value::String(result)) => println!("{}", result),
+        Ok(Value::Null) => {}
+        Ok(result) => println!(
+            "{}",
+            serde_json::to_string_pretty(&result).expect("values always serialize")
+        ),
+        Err(message) => {
+            eprintln!("{}", message);
+            return false;
+        }
+    }
+    true
+}
+
+// e.g. `5s ago`, `3m ago`
+fn ago(started: u64, now: u64) -> String {
+    let seconds = now.saturating_sub(started);
+    match seconds {
+        0..=59 => format!("{}s ago", seconds),
+        60..=3599 => format!("{}m ago", seconds / 60),
+        3600..=86399 => format!("{}h ago", seconds / 3600),
+        _ => format!("{}d ago", seconds / 86400),
+    }
+}
+
+// a run the way `hotkeyd history` prints it, output indented below it
+fn format_record(record: &RunRecord, now: u64) -> String {
+    let outcome = match record.exit_code {
+        Some(code) => format!("exited {}", code),
+        None => serde_json::to_value(record.outcome)
+            .ok()
+            .and_then(|outcome| outcome.as_str().map(str::to_string))
+            .unwrap_or_default(),
+    };
+    let mut lines = vec![format!(
+        "{:>7}  {}  {} in {}ms: {}",
+        ago(record.started, now),
+        record.bind,
+        outcome,
+        record.duration_ms,
+        record.command
+    )];
+    for (name, output) in [("stdout", &record.stdout), ("stderr", &record.stderr)] {
+        for line in output.lines() {
+            lines.push(format!("         {}| {}", name, line));
+        }
+    }
+    lines.join("\n")
+}
+
+// prints the last `limit` commands the daemon ran, oldest first. returns
+// whether the daemon answered.
+pub fn history(limit: usize) -> bool {
+    let records = request("history", json!({ "limit": limit })).and_then(|result| {
+        serde_json::from_value::<Vec<RunRecord>>(result).map_err(|err| err.to_string())
+    });
+    let records = match records {
+        Ok(records) => records,
+        Err(message) => {
+            eprintln!("{}", message);
+            return false;
+        }
+    };
+
+    if records.is_empty() {
+        println!("no commands have run yet");
+    }
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    for record in records.iter() {
+        println!("{}", format_record(record, now));
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use rdev::EventType;
+    use serde_json::{json, Value};
+
+    use super::handle_line;
+    use crate::{
+        app::FakeFocusedApp, config_manager::ConfigManager, handler::Handler, simulate::EventSink,
+    };
+
+    struct NullSink;
+
+    impl EventSink for NullSink {
+        fn send(&self, _: EventType) {}
+    }
+
+    fn request(handler: &Handler, request: Value) -> Value {
+        serde_json::from_str(&handle_line(handler, &request.to_string())).unwrap()
+    }
+
+    #[test]
+    fn requests_are_answered() {
+        let focused_app = Box::new(FakeFocusedApp(Mutex::new(Vec::new())));
+        let config_manager: &'static ConfigManager =
+            Box::leak(Box::new(ConfigManager::new_from_str(
+                r#"
+                [binds."meta-left + key-k, key-t"]
+                type = "keys"
+                keys = ["x"]
+
+                [modes.resize]
+
+                [modes.resize.binds."key-h"]
+                type = "keys"
+                keys = ["x"]
+                "#,
+                focused_app,
+            )));
+        let handler = Handler::new(config_manager, &NullSink);
+
+        assert_eq!(
+            request(&handler, json!({ "id": 1, "method": "list-binds" })),
+            json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "result": ["meta-left + key-k, key-t", "resize: key-h"]
+            })
+        );
+
+        request(&handler, json!({ "id": 2, "method": "pause" }));
+        assert!(handler.is_paused());
+        assert_eq!(
+            request(&handler, json!({ "id": 3, "method": "status" }))["result"]["paused"],
+            json!(true)
+        );
+
+        assert_eq!(
+            request(
+                &handler,
+                json!({ "id": 4, "method": "trigger", "params": { "bind": "meta-left + key-q" } })
+            )["error"]["message"],
+            json!("no bind for `meta-left + key-q`")
+        );
+        // binds of a mode can be triggered the way they are listed
+        assert_eq!(
+            request(
+                &handler,
+                json!({ "id": 5, "method": "trigger", "params": { "bind": "resize: key-h" } })
+            )["result"],
+            json!("triggered")
+        );
+        assert_eq!(
+            request(
+                &handler,
+                json!({ "id": 6, "method": "trigger", "params": { "bind": "move: key-h" } })
+            )["error"]["message"],
+            json!("no mode `move`")
+        );
+        assert_eq!(
+            request(&handler, json!({ "id": 7, "method": "nope" }))["error"]["code"],
+            json!(-32601)
+        );
+    }
+}
@@ -0,0 +1,686 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    fs::OpenOptions,
+    future::pending,
+    path::PathBuf,
+    process::Stdio,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt},
+    process::{Child, Command},
+    runtime::{Builder, Handle, Runtime},
+    sync::oneshot,
+    task::JoinHandle,
+    time::{self, sleep},
+};
+
+use crate::{action::ActionId, utils::get_user};
+
+// what happens when a command is started while an earlier run of it is still
+// going
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Concurrency {
+    // run them side by side
+    #[default]
+    Allow,
+    // ignore the new one
+    DropIfRunning,
+    // kill the running one and start over
+    Restart,
+    // start the new one once the running one is done
+    Queue,
+}
+
+// what a `cmd` action runs
+#[derive(Debug, Clone, PartialEq)]
+pub enum Program {
+    // `command` run with `shell -c`
+    Shell { shell: String, command: String },
+    // run directly without a shell, the first arg is the program
+    Exec(Vec<String>),
+}
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Program::Shell { command, .. } => f.write_str(command),
+            Program::Exec(args) => f.write_str(&args.join(" ")),
+        }
+    }
+}
+
+// how the process of a `cmd` action is started
+#[derive(Debug, Clone, PartialEq)]
+pub struct Process {
+    pub program: Program,
+    pub cwd: Option<PathBuf>,
+    // set on top of the environment of the daemon, or on their own with
+    // `clear_env`
+    pub env: Vec<(String, String)>,
+    pub clear_env: bool,
+    // appended to instead of keeping stdout in the history
+    pub stdout: Option<PathBuf>,
+}
+
+// a single run of a `cmd` action
+#[derive(Debug, Clone)]
+pub struct Job {
+    // the concurrency policy applies to the runs of the same action
+    pub action: ActionId,
+    pub process: Process,
+    // describe what triggered the action, they win over `process.env`
+    pub envs: Vec<(String, String)>,
+    pub concurrency: Concurrency,
+    // the command is killed if it runs for longer than this
+    pub timeout: Option<Duration>,
+    // what triggered it, for the logs
+    pub bind: String,
+}
+
+impl Job {
+    // runs are logged by this
+    pub fn command(&self) -> String {
+        self.process.program.to_string()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Outcome {
+    Exited,
+    TimedOut,
+    Restarted,
+    FailedToStart,
+    // `drop-if-running` and it was still running, it never started
+    Dropped,
+}
+
+// a finished run, logged as a line of json and kept in the history
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunRecord {
+    pub bind: String,
+    pub command: String,
+    // seconds since the unix epoch
+    pub started: u64,
+    pub duration_ms: u64,
+    pub outcome: Outcome,
+    // none if it didn't exit by itself
+    pub exit_code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl RunRecord {
+    pub fn succeeded(&self) -> bool {
+        self.outcome == Outcome::Exited && self.exit_code == Some(0)
+    }
+}
+
+// how many runs `hotkeyd history` can show
+const HISTORY_LEN: usize = 100;
+// how much of stdout and stderr is kept per run
+const MAX_OUTPUT: usize = 64 * 1024;
+// how long to keep reading output after the command exited
+const OUTPUT_GRACE: Duration = Duration::from_millis(100);
+
+// the runs of one action
+#[derive(Default)]
+struct Slot {
+    // sending on one of these kills that run
+    running: HashMap<u64, oneshot::Sender<()>>,
+    queued: VecDeque<Job>,
+}
+
+struct Shared {
+    runtime: Handle,
+    next_id: AtomicU64,
+    // two binds running the same command don't share a slot
+    slots: Mutex<HashMap<ActionId, Slot>>,
+    // the most recent runs, oldest first
+    history: Mutex<VecDeque<RunRecord>>,
+}
+
+// runs the commands of actions and does their waiting. the daemon uses
+// `Executor`, tests use a fake that records what it was asked to do.
+pub trait Runner: Send + Sync {
+    // starts `job` according to its concurrency policy and returns right away
+    fn run(&self, job: Job);
+
+    // runs `job` and waits for it. returns its exit code, none if it was
+    // killed or couldn't be started.
+    fn probe(&self, job: Job) -> Option<i32>;
+
+    fn sleep(&self, duration: Duration);
+}
+
+// runs `cmd` actions on the tokio runtime so a slow command never holds up
+// the grab callback (and with it every key press on the system)
+pub struct Executor {
+    shared: Arc<Shared>,
+    // only set when there was no runtime to borrow (tests)
+    _runtime: Option<Runtime>,
+}
+
+impl Default for Executor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Executor {
+    pub fn new() -> Self {
+        let (runtime, own_runtime) = match Handle::try_current() {
+            Ok(runtime) => (runtime, None),
+            Err(_) => {
+                let runtime = Builder::new_multi_thread()
+                    .worker_threads(1)
+                    .enable_all()
+                    .build()
+                    .expect("couldn't start a runtime for commands");
+                (runtime.handle().clone(), Some(runtime))
+            }
+        };
+        Self {
+            shared: Arc::new(Shared {
+                runtime,
+                next_id: AtomicU64::new(0),
+                slots: Mutex::new(HashMap::new()),
+                history: Mutex::new(VecDeque::with_capacity(HISTORY_LEN)),
+            }),
+            _runtime: own_runtime,
+        }
+    }
+
+    // runs `task` on a thread where it's fine to block
+    pub fn spawn_blocking(&self, task: impl FnOnce() + Send + 'static) {
+        self.shared.runtime.spawn_blocking(task);
+    }
+
+    pub fn history(&self) -> Vec<RunRecord> {
+        let history = self.shared.history.lock().expect("history was poisoned");
+        history.iter().cloned().collect()
+    }
+
+    // how many runs of `action` are going right now
+    #[cfg(test)]
+    pub fn running(&self, action: ActionId) -> usize {
+        let slots = self.shared.slots.lock().expect("slots were poisoned");
+        slots.get(&action).map_or(0, |slot| slot.running.len())
+    }
+}
+
+impl Runner for Executor {
+    fn run(&self, job: Job) {
+        let mut slots = self.shared.slots.lock().expect("slots were poisoned");
+        let slot = slots.entry(job.action).or_default();
+        match job.concurrency {
+            Concurrency::DropIfRunning if !slot.running.is_empty() => {
+                drop(slots);
+                self.shared.log(dropped(&job));
+                return;
+            }
+            Concurrency::Queue if !slot.running.is_empty() => {
+                slot.queued.push_back(job);
+                return;
+            }
+            Concurrency::Restart => {
+                for (_, kill) in slot.running.drain() {
+                    let _ = kill.send(());
+                }
+            }
+            _ => {}
+        }
+        Shared::start(&self.shared, slot, job);
+    }
+
+    // has to be called from outside the runtime, e.g. from `spawn_blocking`
+    fn probe(&self, job: Job) -> Option<i32> {
+        // nothing kills a probe but its timeout
+        let (_kill, killed) = oneshot::channel();
+        let record = self.shared.runtime.block_on(run_job(&job, killed));
+        let exit_code = record.exit_code;
+        self.shared.log(record);
+        exit_code
+    }
+
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration);
+    }
+}
+
+impl Shared {
+    fn start(shared: &Arc<Shared>, slot: &mut Slot, job: Job) {
+        let id = shared.next_id.fetch_add(1, Ordering::Relaxed);
+        let (kill, killed) = oneshot::channel();
+        slot.running.insert(id, kill);
+
+        let shared_for_task = shared.clone();
+        shared.runtime.spawn(async move {
+            let record = run_job(&job, killed).await;
+            shared_for_task.log(record);
+            shared_for_task.finished(job.action, id);
+        });
+    }
+
+    fn log(&self, record: RunRecord) {
+        match serde_json::to_string(&record) {
+            Ok(line) if record.succeeded() || record.outcome == Outcome::Dropped => {
+                println!("{}", line)
+            }
+            Ok(line) => eprintln!("{}", line),
+            Err(err) => eprintln!("error logging run of `{}`: {}", record.command, err),
+        }
+
+        let mut history = self.history.lock().expect("history was poisoned");
+        if history.len() == HISTORY_LEN {
+            history.pop_front();
+        }
+        history.push_back(record);
+    }
+
+    // forgets about a run and starts the next queued one
+    fn finished(self: &Arc<Shared>, action: ActionId, id: u64) {
+        let mut slots = self.slots.lock().expect("slots were poisoned");
+        let Some(slot) = slots.get_mut(&action) else {
+            return;
+        };
+        slot.running.remove(&id);
+        if !slot.running.is_empty() {
+            return;
+        }
+        match slot.queued.pop_front() {
+            Some(job) => Shared::start(self, slot, job),
+            None => {
+                slots.remove(&action);
+            }
+        }
+    }
+}
+
+// kills the command along with everything it started, `sh -c` would leave
+// its children behind otherwise
+fn kill_group(child: &Child) {
+    if let Some(pid) = child.id() {
+        // SAFETY: killpg has no memory safety requirements
+        unsafe {
+            libc::killpg(pid as libc::pid_t, libc::SIGKILL);
+        }
+    }
+}
+
+// reads all of `pipe` into `buffer`, keeping only the first `MAX_OUTPUT`
+// bytes so a chatty command can't eat all the memory
+async fn capture(mut pipe: impl AsyncRead + Unpin, buffer: Arc<Mutex<Vec<u8>>>) {
+    let mut chunk = [0; 4096];
+    loop {
+        let read = match pipe.read(&mut chunk).await {
+            Ok(0) | Err(_) => return,
+            Ok(read) => read,
+        };
+        let mut buffer = buffer.lock().expect("output buffer was poisoned");
+        let room = MAX_OUTPUT.saturating_sub(buffer.len());
+        buffer.extend_from_slice(&chunk[..read.min(room)]);
+    }
+}
+
+fn decode(buffer: &Mutex<Vec<u8>>) -> String {
+    String::from_utf8_lossy(&buffer.lock().expect("output buffer was poisoned")).into_owned()
+}
+
+fn unix_time(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs())
+}
+
+// a run that was never started because the action was still running
+fn dropped(job: &Job) -> RunRecord {
+    RunRecord {
+        bind: job.bind.clone(),
+        command: job.command(),
+        started: unix_time(SystemTime::now()),
+        duration_ms: 0,
+        outcome: Outcome::Dropped,
+        exit_code: None,
+        stdout: String::new(),
+        stderr: String::new(),
+    }
+}
+
+async fn run_job(job: &Job, killed: oneshot::Receiver<()>) -> RunRecord {
+    let started = SystemTime::now();
+    let start = Instant::now();
+    let record = |outcome, exit_code, stdout: String, stderr: String| RunRecord {
+        bind: job.bind.clone(),
+        command: job.command(),
+        started: unix_time(started),
+        duration_ms: start.elapsed().as_millis() as u64,
+        outcome,
+        exit_code,
+        stdout,
+        stderr,
+    };
+
+    let process = &job.process;
+    let mut command = match &process.program {
+        Program::Shell {
+            shell,
+            command: line,
+        } => {
+            let mut command = Command::new(shell);
+            command.args(["-c", line]);
+            command
+        }
+        Program::Exec(args) => {
+            let (program, args) = args.split_first().expect("`args` is never empty");
+            let mut command = Command::new(program);
+            command.args(args);
+            command
+        }
+    };
+
+    if process.clear_env {
+        command.env_clear();
+    } else if let Some(user) = get_user() {
+        // we want to set the USER env var to the currently logged in user
+        command.env("USER", user);
+    }
+    if let Some(cwd) = &process.cwd {
+        command.current_dir(cwd);
+    }
+    command
+        .envs(process.env.iter().cloned())
+        .envs(job.envs.iter().cloned())
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        // its own process group so it can be killed as a whole
+        .process_group(0)
+        .kill_on_drop(true);
+
+    if let Some(path) = &process.stdout {
+        match OpenOptions::new().create(true).append(true).open(path) {
+            Ok(file) => {
+                command.stdout(file);
+            }
+            Err(err) => {
+                let message = format!("error opening {}: {}", path.display(), err);
+                return record(Outcome::FailedToStart, None, String::new(), message);
+            }
+        }
+    }
+
+    let mut child = match command.spawn() {
+        Ok(child) => child,
+        Err(err) => {
+            let message = format!("error running `{}`: {}", job.command(), err);
+            return record(Outcome::FailedToStart, None, String::new(), message);
+        }
+    };
+
+    let stdout = Arc::new(Mutex::new(Vec::new()));
+    let stderr = Arc::new(Mutex::new(Vec::new()));
+    let mut readers = Vec::new();
+    if let Some(pipe) = child.stdout.take() {
+        readers.push(tokio::spawn(capture(pipe, stdout.clone())));
+    }
+    if let Some(pipe) = child.stderr.take() {
+        readers.push(tokio::spawn(capture(pipe, stderr.clone())));
+    }
+
+    let timeout = async {
+        match job.timeout {
+            Some(timeout) => sleep(timeout).await,
+            None => pending().await,
+        }
+    };
+
+    let (outcome, exit_code) = tokio::select! {
+        status = child.wait() => match status {
+            Ok(status) => (Outcome::Exited, status.code()),
+            Err(_) => (Outcome::Exited, None),
+        },
+        _ = timeout => (Outcome::TimedOut, None),
+        // a restart, or the executor went away
+        _ = killed => (Outcome::Restarted, None),
+    };
+    if outcome != Outcome::Exited {
+        kill_group(&child);
+        let _ = child.wait().await;
+    }
+
+    // something the command started in the background can keep the pipes
+    // open long after it exited, we don't wait for that
+    let _ = time::timeout(OUTPUT_GRACE, join_all(readers)).await;
+    record(outcome, exit_code, decode(&stdout), decode(&stderr))
+}
+
+async fn join_all(readers: Vec<JoinHandle<()>>) {
+    for reader in readers {
+        let _ = reader.await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env::temp_dir, fs, process, thread, time::Duration};
+
+    use super::{Concurrency, Executor, Job, Outcome, Process, Program, Runner};
+    use crate::action::ActionId;
+
+    // a command that appends to a file once it's done, so we can tell which
+    // runs finished
+    fn job(name: &str, concurrency: Concurrency) -> (Job, std::path::PathBuf) {
+        let path = temp_dir().join(format!("hotkeyd-executor-{}-{}", process::id(), name));
+        let _ = fs::remove_file(&path);
+        let job = Job {
+            action: ActionId::next(),
+            process: shell(&format!("sleep 0.3; echo done >> {}", path.display())),
+            envs: Vec::new(),
+            concurrency,
+            timeout: None,
+            bind: name.to_string(),
+        };
+        (job, path)
+    }
+
+    fn shell(command: &str) -> Process {
+        Process {
+            program: Program::Shell {
+                shell: "sh".to_string(),
+                command: command.to_string(),
+            },
+            cwd: None,
+            env: Vec::new(),
+            clear_env: false,
+            stdout: None,
+        }
+    }
+
+    fn runs(path: &std::path::Path) -> usize {
+        fs::read_to_string(path).map_or(0, |content| content.lines().count())
+    }
+
+    #[test]
+    fn concurrency_policies_are_followed() {
+        let executor = Executor::new();
+
+        let mut results = Vec::new();
+        for (name, concurrency) in [
+            ("allow", Concurrency::Allow),
+            ("drop", Concurrency::DropIfRunning),
+            ("restart", Concurrency::Restart),
+            ("queue", Concurrency::Queue),
+        ] {
+            let (job, path) = job(name, concurrency);
+            executor.run(job.clone());
+            executor.run(job.clone());
+            results.push((name, path, job.action));
+        }
+
+        // nothing waits for the commands
+        assert_eq!(executor.running(results[0].2), 2);
+        assert_eq!(executor.running(results[1].2), 1);
+        assert_eq!(executor.running(results[3].2), 1);
+
+        thread::sleep(Duration::from_millis(1500));
+        let finished: Vec<(&str, usize)> = results
+            .iter()
+            .map(|(name, path, _)| (*name, runs(path)))
+            .collect();
+        assert_eq!(
+            finished,
+            vec![("allow", 2), ("drop", 1), ("restart", 1), ("queue", 2)]
+        );
+        for (_, path, action) in results.iter() {
+            assert_eq!(executor.running(*action), 0);
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    #[test]
+    fn drops_are_per_action_and_kept_in_the_history() {
+        let executor = Executor::new();
+        let (job, path) = job("drop-per-action", Concurrency::DropIfRunning);
+        // another bind running the same command
+        let other = Job {
+            action: ActionId::next(),
+            bind: "other".to_string(),
+            ..job.clone()
+        };
+        executor.run(job.clone());
+        executor.run(other.clone());
+        executor.run(job.clone());
+        assert_eq!(executor.running(job.action), 1);
+        assert_eq!(executor.running(other.action), 1);
+
+        let dropped: Vec<(String, Outcome)> = executor
+            .history()
+            .into_iter()
+            .map(|run| (run.bind, run.outcome))
+            .collect();
+        assert_eq!(
+            dropped,
+            vec![("drop-per-action".to_string(), Outcome::Dropped)]
+        );
+
+        thread::sleep(Duration::from_millis(600));
+        assert_eq!(runs(&path), 2);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn commands_are_killed_after_their_timeout() {
+        let executor = Executor::new();
+        let (mut job, path) = job("timeout", Concurrency::Allow);
+        job.timeout = Some(Duration::from_millis(50));
+        executor.run(job.clone());
+
+        thread::sleep(Duration::from_millis(600));
+        assert_eq!(runs(&path), 0);
+        assert_eq!(executor.running(job.action), 0);
+    }
+
+    #[test]
+    fn runs_are_kept_with_their_output() {
+        let executor = Executor::new();
+        let (mut job, path) = job("history", Concurrency::Allow);
+        job.process = shell("echo out; echo err >&2; exit 3");
+        executor.run(job.clone());
+
+        // stdout goes to the file instead of the history
+        job.process = shell("echo to file");
+        job.process.stdout = Some(path.clone());
+        executor.run(job.clone());
+
+        thread::sleep(Duration::from_millis(500));
+        let mut history = executor.history();
+        history.sort_by(|a, b| a.command.cmp(&b.command));
+        let runs: Vec<(&str, &str, Option<i32>, &str, &str)> = history
+            .iter()
+            .map(|run| {
+                (
+                    run.bind.as_str(),
+                    run.command.as_str(),
+                    run.exit_code,
+                    run.stdout.as_str(),
+                    run.stderr.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            runs,
+            vec![
+                (
+                    "history",
+                    "echo out; echo err >&2; exit 3",
+                    Some(3),
+                    "out\n",
+                    "err\n"
+                ),
+                ("history", "echo to file", Some(0), "", ""),
+            ]
+        );
+        assert!(history.iter().all(|run| run.outcome == Outcome::Exited));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "to file\n");
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn processes_are_started_as_configured() {
+        let executor = Executor::new();
+        let (mut job, _) = job("process", Concurrency::Allow);
+        let cwd = temp_dir().canonicalize().unwrap();
+        job.process = Process {
+            cwd: Some(cwd.clone()),
+            env: vec![("HOTKEYD_A".to_string(), "a".to_string())],
+            clear_env: true,
+            ..shell("pwd -P; echo \"$HOTKEYD_A|$HOTKEYD_B|$HOME\"")
+        };
+        job.envs = vec![("HOTKEYD_B".to_string(), "b".to_string())];
+        executor.run(job.clone());
+
+        // arguments are passed as they are without a shell in between
+        job.process = Process {
+            program: Program::Exec(vec!["echo".to_string(), "$HOME  x".to_string()]),
+            ..shell("")
+        };
+        executor.run(job.clone());
+
+        thread::sleep(Duration::from_millis(500));
+        let mut outputs: Vec<String> = executor
+            .history()
+            .into_iter()
+            .map(|run| run.stdout)
+            .collect();
+        outputs.sort();
+        assert_eq!(
+            outputs,
+            vec![
+                "$HOME  x\n".to_string(),
+                format!("{}\na|b|\n", cwd.display()),
+            ]
+        );
+    }
+
+    #[test]
+    fn probes_are_waited_for() {
+        let executor = Executor::new();
+        let (mut job, _) = job("probe", Concurrency::Allow);
+        job.process = shell("sleep 0.1; exit 4");
+        assert_eq!(executor.probe(job.clone()), Some(4));
+
+        job.timeout = Some(Duration::from_millis(50));
+        assert_eq!(executor.probe(job), None);
+        assert_eq!(executor.history().len(), 2);
+    }
+}
@@ -0,0 +1,813 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
+
+use rdev::{Event, EventType};
+
+use crate::{
+    config::Bind,
+    config_manager::ConfigManager,
+    key::{ButtonSet, HeldButtons, HeldModifiers, Key, MouseButton, Trigger},
+    mouse::MouseTracker,
+    scroll::ScrollTracker,
+    sequence::SequenceTracker,
+    simulate::{self, EventSink},
+};
+
+// everything the grab callback does lives here so that it can be driven by a
+// recorded stream of events instead of a real keyboard
+pub struct Handler {
+    held_modifiers: Arc<HeldModifiers>,
+    held_buttons: Arc<HeldButtons>,
+    // buttons whose press was blocked, their release has to be blocked too or
+    // the app would see a release without a press
+    blocked_buttons: Mutex<ButtonSet>,
+    sequences: Arc<Mutex<SequenceTracker>>,
+    mouse: Mutex<MouseTracker>,
+    scroll: Mutex<ScrollTracker>,
+    // while paused every event goes through untouched
+    paused: AtomicBool,
+    // the key that fired the pause bind, its release is blocked as well
+    pause_key: Mutex<Option<Trigger>>,
+
+    config_manager: &'static ConfigManager,
+    sink: &'static dyn EventSink,
+}
+
+impl Handler {
+    pub fn new(config_manager: &'static ConfigManager, sink: &'static dyn EventSink) -> Self {
+        let held_modifiers = Arc::new(HeldModifiers::default());
+        let held_buttons = Arc::new(HeldButtons::default());
+        let sequences = Arc::new(Mutex::new(SequenceTracker::new(
+            held_modifiers.clone(),
+            held_buttons.clone(),
+            config_manager,
+            sink,
+        )));
+        Self {
+            held_modifiers,
+            held_buttons,
+            blocked_buttons: Mutex::new(ButtonSet::new()),
+            sequences,
+            mouse: Mutex::new(MouseTracker::default()),
+            scroll: Mutex::new(ScrollTracker::default()),
+            paused: AtomicBool::new(false),
+            pause_key: Mutex::new(None),
+            config_manager,
+            sink,
+        }
+    }
+
+    // returns the event if it should be passed on to the os
+    pub fn handle(&self, event: Event) -> Option<Event> {
+        let event = self.handle_event(event);
+        // what was sent while handling it comes back once we return
+        simulate::settle();
+        event
+    }
+
+    fn handle_event(&self, event: Event) -> Option<Event> {
+        // events we sent ourselves (replayed sequences, etc) go straight through
+        if simulate::is_injected(&event.event_type) {
+            return Some(event);
+        }
+
+        if self.handle_pause_bind(&event.event_type) {
+            return None;
+        }
+
+        if self.is_paused() {
+            // what is held still has to be tracked so the pause bind can be
+            // typed and nothing looks stuck once we resume
+            self.track_held(&event.event_type);
+            if let EventType::ButtonRelease(button) = event.event_type {
+                if self
+                    .blocked_buttons
+                    .lock()
+                    .expect("blocked_buttons was poisoned")
+                    .remove(MouseButton::new_from_rdev(button))
+                {
+                    return None;
+                }
+            }
+            return Some(event);
+        }
+
+        match event.event_type {
+            EventType::KeyPress(key) => match Key::new_from_rdev(key) {
+                Key::Modifier(modifier_key) => {
+                    self.held_modifiers.press(modifier_key);
+                }
+                Key::Keyboard(key) => {
+                    if SequenceTracker::press(&self.sequences, Trigger::Keyboard(key)) {
+                        return None;
+                    }
+                }
+            },
+            EventType::KeyRelease(key) => match Key::new_from_rdev(key) {
+                Key::Modifier(modifier_key) => {
+                    self.held_modifiers.release(modifier_key);
+                }
+                Key::Keyboard(key) => {
+                    if SequenceTracker::release(&self.sequences, Trigger::Keyboard(key)) {
+                        return None;
+                    }
+                }
+            },
+            EventType::ButtonPress(button) => {
+                let button = MouseButton::new_from_rdev(button);
+                let blocked = SequenceTracker::press(&self.sequences, Trigger::Button(button));
+                // only held after the press is handled so a button is never
+                // its own modifier
+                self.held_buttons.press(button);
+                if blocked {
+                    self.blocked_buttons
+                        .lock()
+                        .expect("blocked_buttons was poisoned")
+                        .insert(button);
+                    return None;
+                }
+            }
+            EventType::ButtonRelease(button) => {
+                let button = MouseButton::new_from_rdev(button);
+                self.held_buttons.release(button);
+                let blocked = SequenceTracker::release(&self.sequences, Trigger::Button(button));
+                if self
+                    .blocked_buttons
+                    .lock()
+                    .expect("blocked_buttons was poisoned")
+                    .remove(button)
+                    || blocked
+                {
+                    return None;
+                }
+            }
+            EventType::Wheel { delta_x, delta_y } => {
+                let mut scroll = self.scroll.lock().expect("scroll tracker was poisoned");
+                if self.config_manager.scroll(
+                    self.held_modifiers.get(),
+                    delta_x,
+                    delta_y,
+                    &mut scroll,
+                    self.sink,
+                ) {
+                    return None;
+                }
+            }
+            EventType::MouseMove { x, y } => {
+                let mut mouse = self.mouse.lock().expect("mouse tracker was poisoned");
+                if self.config_manager.mouse_move(
+                    self.held_modifiers.get(),
+                    x,
+                    y,
+                    &mut mouse,
+                    self.sink,
+                ) {
+                    return None;
+                }
+            }
+        }
+        Some(event)
+    }
+
+    pub fn config_manager(&self) -> &'static ConfigManager {
+        self.config_manager
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::SeqCst);
+        println!("paused");
+    }
+
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::SeqCst);
+        println!("resumed");
+    }
+
+    pub fn toggle_pause(&self) {
+        if self.is_paused() {
+            self.resume();
+        } else {
+            self.pause();
+        }
+    }
+
+    // the pause bind has to work while paused too so it is looked for before
+    // anything else. returns true if the event was part of it.
+    fn handle_pause_bind(&self, event_type: &EventType) -> bool {
+        let trigger = match *event_type {
+            EventType::KeyPress(key) | EventType::KeyRelease(key) => {
+                match Key::new_from_rdev(key) {
+                    Key::Keyboard(key) => Trigger::Keyboard(key),
+                    Key::Modifier(_) => return false,
+                }
+            }
+            EventType::ButtonPress(button) | EventType::ButtonRelease(button) => {
+                Trigger::Button(MouseButton::new_from_rdev(button))
+            }
+            _ => return false,
+        };
+
+        let mut pause_key = self.pause_key.lock().expect("pause_key was poisoned");
+        match event_type {
+            EventType::KeyPress(_) | EventType::ButtonPress(_) => {
+                let bind = Bind::new(self.held_modifiers.get(), self.held_buttons.get(), trigger);
+                if !self.config_manager.is_pause_bind(&bind) {
+                    return false;
+                }
+                *pause_key = Some(trigger);
+                self.toggle_pause();
+                true
+            }
+            _ => {
+                if *pause_key != Some(trigger) {
+                    return false;
+                }
+                *pause_key = None;
+                true
+            }
+        }
+    }
+
+    fn track_held(&self, event_type: &EventType) {
+        match *event_type {
+            EventType::KeyPress(key) => {
+                if let Key::Modifier(modifier_key) = Key::new_from_rdev(key) {
+                    self.held_modifiers.press(modifier_key);
+                }
+            }
+            EventType::KeyRelease(key) => {
+                if let Key::Modifier(modifier_key) = Key::new_from_rdev(key) {
+                    self.held_modifiers.release(modifier_key);
+                }
+            }
+            EventType::ButtonPress(button) => {
+                self.held_buttons.press(MouseButton::new_from_rdev(button));
+            }
+            EventType::ButtonRelease(button) => {
+                self.held_buttons
+                    .release(MouseButton::new_from_rdev(button));
+            }
+            _ => {}
+        }
+    }
+
+    // runs the bind written as `keys` without it being typed
+    pub fn trigger(&self, keys: &str) -> Result<(), String> {
+        self.config_manager.trigger(keys, self.sink)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Mutex, time::SystemTime};
+
+    use rdev::{Button, Event, EventType, Key as RDevKey};
+
+    use super::Handler;
+    use crate::{app::FakeFocusedApp, config_manager::ConfigManager, simulate::EventSink};
+
+    struct RecordingSink {
+        events: Mutex<Vec<EventType>>,
+    }
+
+    impl EventSink for RecordingSink {
+        fn send(&self, event_type: EventType) {
+            self.events.lock().unwrap().push(event_type);
+        }
+    }
+
+    fn replay(config: &str, events: &[EventType]) -> Vec<EventType> {
+        replay_in_app(config, "", events)
+    }
+
+    // feeds `events` through a handler the way grab would while `app` is
+    // focused and returns what the os ends up seeing: synthetic events and
+    // the events that were let through, in the order they happened
+    fn replay_in_app(config: &str, app: &str, events: &[EventType]) -> Vec<EventType> {
+        let focused_app = Box::new(FakeFocusedApp(Mutex::new(vec![app.to_string()])));
+        let config_manager: &'static ConfigManager =
+            Box::leak(Box::new(ConfigManager::new_from_str(config, focused_app)));
+        let sink: &'static RecordingSink = Box::leak(Box::new(RecordingSink {
+            events: Mutex::new(Vec::new()),
+        }));
+        let handler = Handler::new(config_manager, sink);
+
+        let mut seen = Vec::new();
+        for event_type in events {
+            let passed = handler.handle(Event {
+                time: SystemTime::now(),
+                name: None,
+                event_type: *event_type,
+            });
+            seen.append(&mut sink.events.lock().unwrap());
+            if let Some(event) = passed {
+                seen.push(event.event_type);
+            }
+        }
+        seen
+    }
+
+    #[test]
+    fn held_modifier_is_left_alone_when_nothing_is_typed() {
+        let seen = replay(
+            r#"
+            [binds."shift-left + slash"]
+            type = "cmd"
+            command = "true"
+            "#,
+            &[
+                EventType::KeyPress(RDevKey::ShiftLeft),
+                EventType::KeyPress(RDevKey::Slash),
+                EventType::KeyRelease(RDevKey::Slash),
+                EventType::KeyRelease(RDevKey::ShiftLeft),
+            ],
+        );
+
+        assert_eq!(
+            seen,
+            vec![
+                // a command can't pick up shift so it is left as is
+                EventType::KeyPress(RDevKey::ShiftLeft),
+                EventType::KeyRelease(RDevKey::Slash),
+                EventType::KeyRelease(RDevKey::ShiftLeft),
+            ]
+        );
+    }
+
+    #[test]
+    fn held_modifier_does_not_leak_into_typed_keys() {
+        let seen = replay(
+            r#"
+            [binds."shift-left + slash"]
+            type = "keys"
+            keys = [":wa", "return"]
+            "#,
+            &[
+                EventType::KeyPress(RDevKey::ShiftLeft),
+                EventType::KeyPress(RDevKey::Slash),
+                EventType::KeyRelease(RDevKey::Slash),
+                EventType::KeyRelease(RDevKey::ShiftLeft),
+            ],
+        );
+
+        // `w` and `a` are typed while shift is up so we get `:wa` and not `:WA`
+        assert_eq!(
+            seen,
+            vec![
+                EventType::KeyPress(RDevKey::ShiftLeft),
+                EventType::KeyRelease(RDevKey::ShiftLeft),
+                EventType::KeyPress(RDevKey::ShiftLeft),
+                EventType::KeyPress(RDevKey::SemiColon),
+                EventType::KeyRelease(RDevKey::SemiColon),
+                EventType::KeyRelease(RDevKey::ShiftLeft),
+                EventType::KeyPress(RDevKey::KeyW),
+                EventType::KeyRelease(RDevKey::KeyW),
+                EventType::KeyPress(RDevKey::KeyA),
+                EventType::KeyRelease(RDevKey::KeyA),
+                EventType::KeyPress(RDevKey::Return),
+                EventType::KeyRelease(RDevKey::Return),
+                EventType::KeyPress(RDevKey::ShiftLeft),
+                EventType::KeyRelease(RDevKey::Slash),
+                EventType::KeyRelease(RDevKey::ShiftLeft),
+            ]
+        );
+    }
+
+    #[test]
+    fn mouse_button_can_trigger_a_bind() {
+        let seen = replay(
+            r#"
+            [binds."control-left + button-middle"]
+            type = "cmd"
+            command = "true"
+            "#,
+            &[
+                EventType::ButtonPress(Button::Middle),
+                EventType::ButtonRelease(Button::Middle),
+                EventType::KeyPress(RDevKey::ControlLeft),
+                EventType::ButtonPress(Button::Middle),
+                EventType::ButtonRelease(Button::Middle),
+                EventType::KeyRelease(RDevKey::ControlLeft),
+            ],
+        );
+
+        assert_eq!(
+            seen,
+            vec![
+                EventType::ButtonPress(Button::Middle),
+                EventType::ButtonRelease(Button::Middle),
+                EventType::KeyPress(RDevKey::ControlLeft),
+                EventType::KeyRelease(RDevKey::ControlLeft),
+            ]
+        );
+    }
+
+    #[test]
+    fn bind_passes_through_in_excluded_app() {
+        let config = r#"
+            [binds."shift-left + slash"]
+            type = "cmd"
+            command = "true"
+            only_in = ["kitty"]
+            "#;
+        let events = [
+            EventType::KeyPress(RDevKey::ShiftLeft),
+            EventType::KeyPress(RDevKey::Slash),
+        ];
+
+        assert_eq!(replay_in_app(config, "Firefox", &events), events.to_vec());
+        assert_eq!(
+            replay_in_app(config, "Kitty", &events),
+            vec![EventType::KeyPress(RDevKey::ShiftLeft)]
+        );
+    }
+
+    #[test]
+    fn bind_fires_outside_excepted_apps() {
+        let config = r#"
+            [binds."shift-left + slash"]
+            type = "cmd"
+            command = "true"
+            except_in = ["kitty", "alacritty"]
+            "#;
+        let events = [
+            EventType::KeyPress(RDevKey::ShiftLeft),
+            EventType::KeyPress(RDevKey::Slash),
+        ];
+
+        assert_eq!(replay_in_app(config, "Kitty", &events), events.to_vec());
+        assert_eq!(replay_in_app(config, "Alacritty", &events), events.to_vec());
+        assert_eq!(
+            replay_in_app(config, "Firefox", &events),
+            vec![EventType::KeyPress(RDevKey::ShiftLeft)]
+        );
+    }
+
+    #[test]
+    fn unbound_keys_pass_through() {
+        let events = [
+            EventType::KeyPress(RDevKey::ShiftLeft),
+            EventType::KeyPress(RDevKey::KeyA),
+            EventType::KeyRelease(RDevKey::KeyA),
+            EventType::KeyRelease(RDevKey::ShiftLeft),
+        ];
+        let seen = replay(
+            r#"
+            [binds."shift-left + slash"]
+            type = "cmd"
+            command = "true"
+            "#,
+            &events,
+        );
+
+        assert_eq!(seen, events.to_vec());
+    }
+
+    #[test]
+    fn scroll_is_rescaled_while_modifier_is_held() {
+        let seen = replay(
+            r#"
+            [scroll."shift-left"]
+            multiply = 3
+            invert_x = true
+            "#,
+            &[
+                EventType::Wheel {
+                    delta_x: 1,
+                    delta_y: 1,
+                },
+                EventType::KeyPress(RDevKey::ShiftLeft),
+                EventType::Wheel {
+                    delta_x: 1,
+                    delta_y: 2,
+                },
+            ],
+        );
+
+        assert_eq!(
+            seen,
+            vec![
+                EventType::Wheel {
+                    delta_x: 1,
+                    delta_y: 1,
+                },
+                EventType::KeyPress(RDevKey::ShiftLeft),
+                EventType::Wheel {
+                    delta_x: -3,
+                    delta_y: 6,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn slow_scroll_adds_up_to_whole_steps() {
+        let wheel = EventType::Wheel {
+            delta_x: 1,
+            delta_y: 1,
+        };
+        let seen = replay(
+            r#"
+            [scroll."shift-left"]
+            multiply = 0.3
+            "#,
+            &[
+                EventType::KeyPress(RDevKey::ShiftLeft),
+                wheel,
+                wheel,
+                wheel,
+                wheel,
+            ],
+        );
+
+        // 0.3, 0.6 and 0.9 are dropped, 1.2 is one step with 0.2 left over
+        assert_eq!(
+            seen,
+            vec![
+                EventType::KeyPress(RDevKey::ShiftLeft),
+                EventType::Wheel {
+                    delta_x: 1,
+                    delta_y: 1,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn mouse_macro_gets_motion_and_is_rate_limited() {
+        let out = std::env::temp_dir().join(format!("hotkeyd-mouse-{}", std::process::id()));
+        let config = format!(
+            r#"
+            [mouse."meta-left"]
+            type = "cmd"
+            command = "echo $HOTKEYD_X $HOTKEYD_DX $HOTKEYD_DY $HOTKEYD_DIRECTION >> {}"
+            rate_limit_ms = 60000
+            block = true
+            "#,
+            out.display()
+        );
+        let seen = replay(
+            &config,
+            &[
+                EventType::MouseMove { x: 0.0, y: 0.0 },
+                EventType::KeyPress(RDevKey::MetaLeft),
+                EventType::MouseMove { x: 10.0, y: 5.0 },
+                EventType::MouseMove { x: 20.0, y: 5.0 },
+            ],
+        );
+        // commands run in the background, give it a moment
+        let mut ran = String::new();
+        for _ in 0..50 {
+            ran = std::fs::read_to_string(&out).unwrap_or_default();
+            if !ran.is_empty() {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+        std::fs::remove_file(&out).unwrap();
+
+        // the second move is within the rate limit so the action only ran once
+        assert_eq!(ran, "10 10 5 right\n");
+        // and meta doesn't flicker on every move since nothing is typed
+        assert_eq!(
+            seen,
+            vec![
+                EventType::MouseMove { x: 0.0, y: 0.0 },
+                EventType::KeyPress(RDevKey::MetaLeft),
+            ]
+        );
+    }
+
+    #[test]
+    fn waiting_actions_run_in_the_background() {
+        let out = std::env::temp_dir().join(format!("hotkeyd-waiting-{}", std::process::id()));
+        let config = format!(
+            r#"
+            [binds."meta-left + key-w"]
+            type = "sequence"
+            actions = [
+                {{ type = "sleep", duration_ms = 10 }},
+                {{ type = "if", command = "exit 1", else = {{ type = "cmd", command = "echo else >> {}" }} }},
+            ]
+            "#,
+            out.display()
+        );
+        let seen = replay(
+            &config,
+            &[
+                EventType::KeyPress(RDevKey::MetaLeft),
+                EventType::KeyPress(RDevKey::KeyW),
+            ],
+        );
+        let mut ran = String::new();
+        for _ in 0..50 {
+            ran = std::fs::read_to_string(&out).unwrap_or_default();
+            if !ran.is_empty() {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+        std::fs::remove_file(&out).unwrap();
+
+        assert_eq!(ran, "else\n");
+        // nothing is typed so the modifier is left alone
+        assert_eq!(seen, vec![EventType::KeyPress(RDevKey::MetaLeft)]);
+    }
+
+    #[test]
+    fn abandoned_sequence_is_replayed() {
+        let seen = replay(
+            r#"
+            [binds."meta-left + key-k, key-t"]
+            type = "cmd"
+            command = "true"
+            "#,
+            &[
+                EventType::KeyPress(RDevKey::MetaLeft),
+                EventType::KeyPress(RDevKey::KeyK),
+                EventType::KeyRelease(RDevKey::KeyK),
+                EventType::KeyRelease(RDevKey::MetaLeft),
+                EventType::KeyPress(RDevKey::KeyX),
+            ],
+        );
+
+        assert_eq!(
+            seen,
+            vec![
+                EventType::KeyPress(RDevKey::MetaLeft),
+                EventType::KeyRelease(RDevKey::MetaLeft),
+                EventType::KeyPress(RDevKey::MetaLeft),
+                EventType::KeyPress(RDevKey::KeyK),
+                EventType::KeyRelease(RDevKey::KeyK),
+                EventType::KeyRelease(RDevKey::MetaLeft),
+                EventType::KeyPress(RDevKey::KeyX),
+            ]
+        );
+    }
+
+    #[test]
+    fn completed_sequence_is_swallowed() {
+        let seen = replay(
+            r#"
+            [binds."meta-left + key-k, key-t"]
+            type = "cmd"
+            command = "true"
+            "#,
+            &[
+                EventType::KeyPress(RDevKey::MetaLeft),
+                EventType::KeyPress(RDevKey::KeyK),
+                EventType::KeyRelease(RDevKey::KeyK),
+                EventType::KeyRelease(RDevKey::MetaLeft),
+                EventType::KeyPress(RDevKey::KeyT),
+                EventType::KeyRelease(RDevKey::KeyT),
+            ],
+        );
+
+        assert_eq!(
+            seen,
+            vec![
+                EventType::KeyPress(RDevKey::MetaLeft),
+                EventType::KeyRelease(RDevKey::MetaLeft),
+                EventType::KeyRelease(RDevKey::KeyT),
+            ]
+        );
+    }
+
+    #[test]
+    fn pause_bind_passes_everything_through_until_pressed_again() {
+        let toggle = [
+            EventType::KeyPress(RDevKey::ControlLeft),
+            EventType::KeyPress(RDevKey::Alt),
+            EventType::KeyPress(RDevKey::KeyP),
+            EventType::KeyRelease(RDevKey::KeyP),
+            EventType::KeyRelease(RDevKey::Alt),
+            EventType::KeyRelease(RDevKey::ControlLeft),
+        ];
+        let bind = [
+            EventType::KeyPress(RDevKey::ControlLeft),
+            EventType::KeyPress(RDevKey::KeyQ),
+            EventType::KeyRelease(RDevKey::KeyQ),
+            EventType::KeyRelease(RDevKey::ControlLeft),
+        ];
+        let seen = replay(
+            r#"
+            [settings]
+            pause_bind = "control-left + alt + key-p"
+
+            [binds."control-left + key-q"]
+            type = "keys"
+            keys = ["x"]
+            "#,
+            &[&toggle[..], &bind, &toggle, &bind].concat(),
+        );
+
+        let modifiers_only = [
+            EventType::KeyPress(RDevKey::ControlLeft),
+            EventType::KeyPress(RDevKey::Alt),
+            EventType::KeyRelease(RDevKey::Alt),
+            EventType::KeyRelease(RDevKey::ControlLeft),
+        ];
+        let bind_ran = [
+            EventType::KeyPress(RDevKey::ControlLeft),
+            EventType::KeyRelease(RDevKey::ControlLeft),
+            EventType::KeyPress(RDevKey::KeyX),
+            EventType::KeyRelease(RDevKey::KeyX),
+            EventType::KeyPress(RDevKey::ControlLeft),
+            EventType::KeyRelease(RDevKey::KeyQ),
+            EventType::KeyRelease(RDevKey::ControlLeft),
+        ];
+        assert_eq!(
+            seen,
+            [&modifiers_only[..], &bind, &modifiers_only, &bind_ran].concat()
+        );
+    }
+
+    #[test]
+    fn mode_replaces_binds_and_captures_unbound_keys() {
+        let seen = replay(
+            r#"
+            [binds."meta-left + key-r"]
+            type = "mode"
+            name = "resize"
+
+            [modes.resize]
+            capture = true
+            escape = "escape"
+
+            [modes.resize.binds."key-h"]
+            type = "keys"
+            keys = ["x"]
+            "#,
+            &[
+                EventType::KeyPress(RDevKey::MetaLeft),
+                EventType::KeyPress(RDevKey::KeyR),
+                EventType::KeyRelease(RDevKey::KeyR),
+                EventType::KeyRelease(RDevKey::MetaLeft),
+                EventType::KeyPress(RDevKey::KeyH),
+                EventType::KeyRelease(RDevKey::KeyH),
+                EventType::KeyPress(RDevKey::KeyJ),
+                EventType::KeyRelease(RDevKey::KeyJ),
+                EventType::KeyPress(RDevKey::Escape),
+                EventType::KeyRelease(RDevKey::Escape),
+                EventType::KeyPress(RDevKey::KeyH),
+                EventType::KeyRelease(RDevKey::KeyH),
+            ],
+        );
+
+        // `key-j` and the escape are swallowed along with their releases
+        assert_eq!(
+            seen,
+            vec![
+                EventType::KeyPress(RDevKey::MetaLeft),
+                EventType::KeyRelease(RDevKey::KeyR),
+                EventType::KeyRelease(RDevKey::MetaLeft),
+                EventType::KeyPress(RDevKey::KeyX),
+                EventType::KeyRelease(RDevKey::KeyX),
+                EventType::KeyRelease(RDevKey::KeyH),
+                EventType::KeyPress(RDevKey::KeyH),
+                EventType::KeyRelease(RDevKey::KeyH),
+            ]
+        );
+    }
+
+    #[test]
+    fn alias_matches_either_side() {
+        let seen = replay(
+            r#"
+            [binds."ctrl + key-a"]
+            type = "keys"
+            keys = ["x"]
+            "#,
+            &[
+                EventType::KeyPress(RDevKey::ControlLeft),
+                EventType::KeyPress(RDevKey::KeyA),
+                EventType::KeyRelease(RDevKey::ControlLeft),
+                EventType::KeyPress(RDevKey::ControlRight),
+                EventType::KeyPress(RDevKey::KeyA),
+            ],
+        );
+
+        assert_eq!(
+            seen,
+            vec![
+                EventType::KeyPress(RDevKey::ControlLeft),
+                EventType::KeyRelease(RDevKey::ControlLeft),
+                EventType::KeyPress(RDevKey::KeyX),
+                EventType::KeyRelease(RDevKey::KeyX),
+                EventType::KeyPress(RDevKey::ControlLeft),
+                EventType::KeyRelease(RDevKey::ControlLeft),
+                EventType::KeyPress(RDevKey::ControlRight),
+                EventType::KeyRelease(RDevKey::ControlRight),
+                EventType::KeyPress(RDevKey::KeyX),
+                EventType::KeyRelease(RDevKey::KeyX),
+                EventType::KeyPress(RDevKey::ControlRight),
+            ]
+        );
+    }
+}
@@ -1,5 +1,12 @@
+use std::{
+    fmt,
+    marker::PhantomData,
+    sync::atomic::{AtomicU16, AtomicU64, Ordering as AtomicOrdering},
+};
+
 use hotkeyd_derive::{ConfigKebabCase, EnumIndex};
-use rdev::Key as RDevKey;
+use rdev::{Button as RDevButton, EventType, Key as RDevKey};
+use serde::{de::Error, Deserialize, Deserializer};
 
 #[derive(Debug)]
 pub enum Key {
@@ -118,8 +125,773 @@ impl Key {
             RDevKey::Kp8 => Self::Keyboard(KeyboardKey::Kp8),
             RDevKey::Kp9 => Self::Keyboard(KeyboardKey::Kp9),
             RDevKey::KpDelete => Self::Keyboard(KeyboardKey::KpDelete),
-            RDevKey::Unknown(code) => Self::Keyboard(KeyboardKey::Unknown(code)),
+            RDevKey::Unknown(code) => Self::Keyboard(KeyboardKey::from_code(code)),
+        }
+    }
+}
+
+// the key or button that fires a bind. everything else in a bind has to be
+// held down before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Trigger {
+    Keyboard(KeyboardKey),
+    Button(MouseButton),
+}
+
+impl Trigger {
+    pub fn press(self) -> EventType {
+        match self {
+            Trigger::Keyboard(key) => EventType::KeyPress(key.to_rdev()),
+            Trigger::Button(button) => EventType::ButtonPress(button.to_rdev()),
+        }
+    }
+
+    pub fn release(self) -> EventType {
+        match self {
+            Trigger::Keyboard(key) => EventType::KeyRelease(key.to_rdev()),
+            Trigger::Button(button) => EventType::ButtonRelease(button.to_rdev()),
+        }
+    }
+}
+
+// the name to write in the config
+impl fmt::Display for Trigger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Trigger::Keyboard(key) => key.fmt(f),
+            Trigger::Button(button) => button.fmt(f),
+        }
+    }
+}
+
+// a single chord that we type on behalf of the user, e.g. `meta-left + key-c`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyStroke {
+    pub modifiers: Vec<ModifierKey>,
+    pub key: KeyboardKey,
+}
+
+impl KeyStroke {
+    pub fn from_config_kebab(chord: &str) -> Option<Self> {
+        let mut modifiers = Vec::new();
+        let mut key = None;
+        for part in chord.split(" + ") {
+            if let Some(modifier) = ModifierKey::from_config_kebab(part) {
+                modifiers.push(modifier);
+                continue;
+            }
+            if let Some(alias) = ModifierAlias::from_config_kebab(part) {
+                modifiers.push(alias.left());
+                continue;
+            }
+            if key.is_some() {
+                return None;
+            }
+            key = Some(KeyboardKey::from_config_name(part)?);
+        }
+
+        Some(Self {
+            modifiers,
+            key: key?,
+        })
+    }
+
+    // the stroke that types `c` on a us layout
+    pub fn from_char(c: char) -> Option<Self> {
+        let shifted = |key| Self {
+            modifiers: vec![ModifierKey::ShiftLeft],
+            key,
+        };
+        let plain = |key| Self {
+            modifiers: Vec::new(),
+            key,
+        };
+
+        let stroke = match c {
+            'a'..='z' => plain(KeyboardKey::from_config_kebab(&format!("key-{}", c))?),
+            'A'..='Z' => shifted(KeyboardKey::from_config_kebab(&format!(
+                "key-{}",
+                c.to_ascii_lowercase()
+            ))?),
+            '0'..='9' => plain(KeyboardKey::from_config_kebab(&format!("num-{}", c))?),
+            ' ' => plain(KeyboardKey::Space),
+            '\n' => plain(KeyboardKey::Return),
+            '\t' => plain(KeyboardKey::Tab),
+            '!' => shifted(KeyboardKey::Num1),
+            '@' => shifted(KeyboardKey::Num2),
+            '#' => shifted(KeyboardKey::Num3),
+            '$' => shifted(KeyboardKey::Num4),
+            '%' => shifted(KeyboardKey::Num5),
+            '^' => shifted(KeyboardKey::Num6),
+            '&' => shifted(KeyboardKey::Num7),
+            '*' => shifted(KeyboardKey::Num8),
+            '(' => shifted(KeyboardKey::Num9),
+            ')' => shifted(KeyboardKey::Num0),
+            '-' => plain(KeyboardKey::Minus),
+            '_' => shifted(KeyboardKey::Minus),
+            '=' => plain(KeyboardKey::Equal),
+            '+' => shifted(KeyboardKey::Equal),
+            '[' => plain(KeyboardKey::LeftBracket),
+            '{' => shifted(KeyboardKey::LeftBracket),
+            ']' => plain(KeyboardKey::RightBracket),
+            '}' => shifted(KeyboardKey::RightBracket),
+            '\\' => plain(KeyboardKey::BackSlash),
+            '|' => shifted(KeyboardKey::BackSlash),
+            ';' => plain(KeyboardKey::SemiColon),
+            ':' => shifted(KeyboardKey::SemiColon),
+            '\'' => plain(KeyboardKey::Quote),
+            '"' => shifted(KeyboardKey::Quote),
+            '`' => plain(KeyboardKey::BackQuote),
+            '~' => shifted(KeyboardKey::BackQuote),
+            ',' => plain(KeyboardKey::Comma),
+            '<' => shifted(KeyboardKey::Comma),
+            '.' => plain(KeyboardKey::Dot),
+            '>' => shifted(KeyboardKey::Dot),
+            '/' => plain(KeyboardKey::Slash),
+            '?' => shifted(KeyboardKey::Slash),
+            _ => return None,
+        };
+        Some(stroke)
+    }
+}
+
+impl ModifierKey {
+    pub fn to_rdev(self) -> RDevKey {
+        match self {
+            ModifierKey::ShiftRight => RDevKey::ShiftRight,
+            ModifierKey::AltGr => RDevKey::AltGr,
+            ModifierKey::MetaRight => RDevKey::MetaRight,
+            ModifierKey::MetaLeft => RDevKey::MetaLeft,
+            ModifierKey::Alt => RDevKey::Alt,
+            ModifierKey::ControlLeft => RDevKey::ControlLeft,
+            ModifierKey::ControlRight => RDevKey::ControlRight,
+            ModifierKey::Function => RDevKey::Function,
+            ModifierKey::ShiftLeft => RDevKey::ShiftLeft,
+            ModifierKey::CapsLock => RDevKey::CapsLock,
+        }
+    }
+
+    pub fn alias(self) -> Option<ModifierAlias> {
+        match self {
+            ModifierKey::ShiftLeft | ModifierKey::ShiftRight => Some(ModifierAlias::Shift),
+            ModifierKey::ControlLeft | ModifierKey::ControlRight => Some(ModifierAlias::Ctrl),
+            ModifierKey::MetaLeft | ModifierKey::MetaRight => Some(ModifierAlias::Meta),
+            ModifierKey::Alt | ModifierKey::AltGr => Some(ModifierAlias::AnyAlt),
+            ModifierKey::Function | ModifierKey::CapsLock => None,
+        }
+    }
+}
+
+impl ModifierAlias {
+    // the key we press when an alias has to be typed
+    pub fn left(self) -> ModifierKey {
+        match self {
+            ModifierAlias::Shift => ModifierKey::ShiftLeft,
+            ModifierAlias::Ctrl => ModifierKey::ControlLeft,
+            ModifierAlias::Meta => ModifierKey::MetaLeft,
+            ModifierAlias::AnyAlt => ModifierKey::Alt,
+        }
+    }
+
+    // the keys the alias stands for
+    pub fn keys(self) -> ModifierSet<ModifierKey> {
+        let (left, right) = match self {
+            ModifierAlias::Shift => (ModifierKey::ShiftLeft, ModifierKey::ShiftRight),
+            ModifierAlias::Ctrl => (ModifierKey::ControlLeft, ModifierKey::ControlRight),
+            ModifierAlias::Meta => (ModifierKey::MetaLeft, ModifierKey::MetaRight),
+            ModifierAlias::AnyAlt => (ModifierKey::Alt, ModifierKey::AltGr),
+        };
+        [left, right].into_iter().collect()
+    }
+}
+
+// the two kinds of modifiers a `ModifierSet` can hold
+pub trait Modifier: Copy {
+    const COUNT: u32;
+
+    fn index(&self) -> u32;
+    fn from_index(index: u32) -> Option<Self>;
+}
+
+macro_rules! impl_modifier {
+    ($($name:ident),*) => {
+        $(
+            // every modifier needs a bit
+            const _: () = assert!($name::COUNT <= u16::BITS);
+
+            impl Modifier for $name {
+                const COUNT: u32 = $name::COUNT;
+
+                fn index(&self) -> u32 {
+                    $name::index(self)
+                }
+
+                fn from_index(index: u32) -> Option<Self> {
+                    $name::from_index(index)
+                }
+            }
+        )*
+    };
+}
+
+impl_modifier!(ModifierKey, ModifierAlias);
+
+// a set of modifiers as a bitset over their `index()`. it is `Copy` and never
+// allocates so it can be built on every key press.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ModifierSet<M> {
+    bits: u16,
+    _modifier: PhantomData<M>,
+}
+
+impl<M: Modifier> ModifierSet<M> {
+    pub const fn new() -> Self {
+        Self::from_bits(0)
+    }
+
+    const fn from_bits(bits: u16) -> Self {
+        Self {
+            bits,
+            _modifier: PhantomData,
+        }
+    }
+
+    fn bit(modifier: M) -> u16 {
+        1 << modifier.index()
+    }
+
+    // returns false if it was already in the set
+    pub fn insert(&mut self, modifier: M) -> bool {
+        let inserted = !self.contains(modifier);
+        self.bits |= Self::bit(modifier);
+        inserted
+    }
+
+    pub fn remove(&mut self, modifier: M) -> bool {
+        let removed = self.contains(modifier);
+        self.bits &= !Self::bit(modifier);
+        removed
+    }
+
+    pub fn contains(&self, modifier: M) -> bool {
+        self.bits & Self::bit(modifier) != 0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bits == 0
+    }
+
+    pub fn len(&self) -> u32 {
+        self.bits.count_ones()
+    }
+
+    // the modifiers in `self` that aren't in `other`
+    pub fn difference(self, other: Self) -> Self {
+        Self::from_bits(self.bits & !other.bits)
+    }
+
+    pub fn intersection(self, other: Self) -> Self {
+        Self::from_bits(self.bits & other.bits)
+    }
+
+    pub fn union(self, other: Self) -> Self {
+        Self::from_bits(self.bits | other.bits)
+    }
+
+    // in the order the modifiers are declared
+    pub fn iter(&self) -> impl Iterator<Item = M> {
+        let bits = self.bits;
+        (0..M::COUNT)
+            .filter(move |index| bits & (1 << index) != 0)
+            .filter_map(M::from_index)
+    }
+}
+
+impl<M: Modifier> Default for ModifierSet<M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<M: Modifier> FromIterator<M> for ModifierSet<M> {
+    fn from_iter<I: IntoIterator<Item = M>>(modifiers: I) -> Self {
+        let mut set = Self::new();
+        for modifier in modifiers {
+            set.insert(modifier);
+        }
+        set
+    }
+}
+
+impl<M: Modifier + fmt::Debug> fmt::Debug for ModifierSet<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+// the modifiers held down right now. the grab callback updates it and the
+// threads replaying sequences read it, neither has to take a lock.
+#[derive(Default)]
+pub struct HeldModifiers(AtomicU16);
+
+impl HeldModifiers {
+    pub fn press(&self, modifier: ModifierKey) {
+        self.0
+            .fetch_or(ModifierSet::bit(modifier), AtomicOrdering::Relaxed);
+    }
+
+    pub fn release(&self, modifier: ModifierKey) {
+        self.0
+            .fetch_and(!ModifierSet::bit(modifier), AtomicOrdering::Relaxed);
+    }
+
+    pub fn get(&self) -> ModifierSet<ModifierKey> {
+        ModifierSet::from_bits(self.0.load(AtomicOrdering::Relaxed))
+    }
+}
+
+// one bit for each named mouse button by its `index()` and one for each code
+// an unknown button can have after them
+const BUTTON_BITS: usize = MouseButton::COUNT as usize - 1 + u8::MAX as usize + 1;
+const BUTTON_WORDS: usize = BUTTON_BITS.div_ceil(u64::BITS as usize);
+
+// a set of mouse buttons, a bitset like `ModifierSet`. it is wider since
+// buttons can have any code but still `Copy`.
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ButtonSet([u64; BUTTON_WORDS]);
+
+impl ButtonSet {
+    pub const fn new() -> Self {
+        Self([0; BUTTON_WORDS])
+    }
+
+    // the word of the bitset the button is in and its bit in that word
+    fn position(button: MouseButton) -> (usize, u64) {
+        let bit = button.bit();
+        (bit / u64::BITS as usize, 1 << (bit % u64::BITS as usize))
+    }
+
+    // returns false if it was already in the set
+    pub fn insert(&mut self, button: MouseButton) -> bool {
+        let inserted = !self.contains(button);
+        let (word, bit) = Self::position(button);
+        self.0[word] |= bit;
+        inserted
+    }
+
+    pub fn remove(&mut self, button: MouseButton) -> bool {
+        let removed = self.contains(button);
+        let (word, bit) = Self::position(button);
+        self.0[word] &= !bit;
+        removed
+    }
+
+    pub fn contains(&self, button: MouseButton) -> bool {
+        let (word, bit) = Self::position(button);
+        self.0[word] & bit != 0
+    }
+
+    // named buttons in the order they are declared, then unknown ones by code
+    pub fn iter(&self) -> impl Iterator<Item = MouseButton> {
+        let words = self.0;
+        (0..BUTTON_BITS)
+            .filter(move |bit| {
+                words[bit / u64::BITS as usize] & (1 << (bit % u64::BITS as usize)) != 0
+            })
+            .map(MouseButton::from_bit)
+    }
+}
+
+impl FromIterator<MouseButton> for ButtonSet {
+    fn from_iter<I: IntoIterator<Item = MouseButton>>(buttons: I) -> Self {
+        let mut set = Self::new();
+        for button in buttons {
+            set.insert(button);
         }
+        set
+    }
+}
+
+impl fmt::Debug for ButtonSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+// the mouse buttons held down right now, shared the same way as
+// `HeldModifiers`
+#[derive(Default)]
+pub struct HeldButtons([AtomicU64; BUTTON_WORDS]);
+
+impl HeldButtons {
+    pub fn press(&self, button: MouseButton) {
+        let (word, bit) = ButtonSet::position(button);
+        self.0[word].fetch_or(bit, AtomicOrdering::Relaxed);
+    }
+
+    pub fn release(&self, button: MouseButton) {
+        let (word, bit) = ButtonSet::position(button);
+        self.0[word].fetch_and(!bit, AtomicOrdering::Relaxed);
+    }
+
+    pub fn get(&self) -> ButtonSet {
+        ButtonSet(std::array::from_fn(|word| {
+            self.0[word].load(AtomicOrdering::Relaxed)
+        }))
+    }
+}
+
+// the modifiers of a bind or macro as they are written in the config
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Modifiers {
+    pub exact: ModifierSet<ModifierKey>,
+    // held if either side is
+    pub aliases: ModifierSet<ModifierAlias>,
+}
+
+impl Modifiers {
+    pub fn is_empty(&self) -> bool {
+        self.exact.is_empty() && self.aliases.is_empty()
+    }
+
+    // whether `held` is what these modifiers describe. every alias needs at
+    // least one of its keys held that isn't already named exactly, the
+    // rest of what is held has to be named exactly.
+    pub fn matches(&self, held: ModifierSet<ModifierKey>) -> bool {
+        let mut aliased = ModifierSet::new();
+        for alias in self.aliases.iter() {
+            let keys = alias.keys().intersection(held).difference(self.exact);
+            if keys.is_empty() {
+                return false;
+            }
+            aliased = aliased.union(keys);
+        }
+        held.difference(aliased) == self.exact
+    }
+
+    // whether some set of held modifiers matches both. whatever is held
+    // beyond the exact modifiers has to be covered by aliases so only the
+    // keys of the aliases have to be tried.
+    pub fn overlaps(&self, other: &Modifiers) -> bool {
+        let exact = self.exact.union(other.exact);
+        let aliased: Vec<ModifierKey> = self
+            .aliases
+            .union(other.aliases)
+            .iter()
+            .flat_map(|alias| alias.keys().iter())
+            .collect();
+        (0..1u32 << aliased.len()).any(|subset| {
+            let held = aliased
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| subset & (1 << i) != 0)
+                .map(|(_, key)| *key)
+                .collect::<ModifierSet<_>>()
+                .union(exact);
+            self.matches(held) && other.matches(held)
+        })
+    }
+}
+
+// aliases first, the way `shift + control-left` is usually written
+impl fmt::Display for Modifiers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<String> = self
+            .aliases
+            .iter()
+            .map(|m| m.to_string())
+            .chain(self.exact.iter().map(|m| m.to_string()))
+            .collect();
+        write!(f, "{}", names.join(" + "))
+    }
+}
+
+impl KeyboardKey {
+    pub fn to_rdev(self) -> RDevKey {
+        match self {
+            KeyboardKey::Backspace => RDevKey::Backspace,
+            KeyboardKey::Delete => RDevKey::Delete,
+            KeyboardKey::DownArrow => RDevKey::DownArrow,
+            KeyboardKey::End => RDevKey::End,
+            KeyboardKey::Escape => RDevKey::Escape,
+            KeyboardKey::F1 => RDevKey::F1,
+            KeyboardKey::F10 => RDevKey::F10,
+            KeyboardKey::F11 => RDevKey::F11,
+            KeyboardKey::F12 => RDevKey::F12,
+            KeyboardKey::F2 => RDevKey::F2,
+            KeyboardKey::F3 => RDevKey::F3,
+            KeyboardKey::F4 => RDevKey::F4,
+            KeyboardKey::F5 => RDevKey::F5,
+            KeyboardKey::F6 => RDevKey::F6,
+            KeyboardKey::F7 => RDevKey::F7,
+            KeyboardKey::F8 => RDevKey::F8,
+            KeyboardKey::F9 => RDevKey::F9,
+            KeyboardKey::Home => RDevKey::Home,
+            KeyboardKey::LeftArrow => RDevKey::LeftArrow,
+            KeyboardKey::PageDown => RDevKey::PageDown,
+            KeyboardKey::PageUp => RDevKey::PageUp,
+            KeyboardKey::Return => RDevKey::Return,
+            KeyboardKey::RightArrow => RDevKey::RightArrow,
+            KeyboardKey::Space => RDevKey::Space,
+            KeyboardKey::Tab => RDevKey::Tab,
+            KeyboardKey::UpArrow => RDevKey::UpArrow,
+            KeyboardKey::PrintScreen => RDevKey::PrintScreen,
+            KeyboardKey::ScrollLock => RDevKey::ScrollLock,
+            KeyboardKey::Pause => RDevKey::Pause,
+            KeyboardKey::NumLock => RDevKey::NumLock,
+            KeyboardKey::BackQuote => RDevKey::BackQuote,
+            KeyboardKey::Num1 => RDevKey::Num1,
+            KeyboardKey::Num2 => RDevKey::Num2,
+            KeyboardKey::Num3 => RDevKey::Num3,
+            KeyboardKey::Num4 => RDevKey::Num4,
+            KeyboardKey::Num5 => RDevKey::Num5,
+            KeyboardKey::Num6 => RDevKey::Num6,
+            KeyboardKey::Num7 => RDevKey::Num7,
+            KeyboardKey::Num8 => RDevKey::Num8,
+            KeyboardKey::Num9 => RDevKey::Num9,
+            KeyboardKey::Num0 => RDevKey::Num0,
+            KeyboardKey::Minus => RDevKey::Minus,
+            KeyboardKey::Equal => RDevKey::Equal,
+            KeyboardKey::KeyQ => RDevKey::KeyQ,
+            KeyboardKey::KeyW => RDevKey::KeyW,
+            KeyboardKey::KeyE => RDevKey::KeyE,
+            KeyboardKey::KeyR => RDevKey::KeyR,
+            KeyboardKey::KeyT => RDevKey::KeyT,
+            KeyboardKey::KeyY => RDevKey::KeyY,
+            KeyboardKey::KeyU => RDevKey::KeyU,
+            KeyboardKey::KeyI => RDevKey::KeyI,
+            KeyboardKey::KeyO => RDevKey::KeyO,
+            KeyboardKey::KeyP => RDevKey::KeyP,
+            KeyboardKey::LeftBracket => RDevKey::LeftBracket,
+            KeyboardKey::RightBracket => RDevKey::RightBracket,
+            KeyboardKey::KeyA => RDevKey::KeyA,
+            KeyboardKey::KeyS => RDevKey::KeyS,
+            KeyboardKey::KeyD => RDevKey::KeyD,
+            KeyboardKey::KeyF => RDevKey::KeyF,
+            KeyboardKey::KeyG => RDevKey::KeyG,
+            KeyboardKey::KeyH => RDevKey::KeyH,
+            KeyboardKey::KeyJ => RDevKey::KeyJ,
+            KeyboardKey::KeyK => RDevKey::KeyK,
+            KeyboardKey::KeyL => RDevKey::KeyL,
+            KeyboardKey::SemiColon => RDevKey::SemiColon,
+            KeyboardKey::Quote => RDevKey::Quote,
+            KeyboardKey::BackSlash => RDevKey::BackSlash,
+            KeyboardKey::IntlBackslash => RDevKey::IntlBackslash,
+            KeyboardKey::KeyZ => RDevKey::KeyZ,
+            KeyboardKey::KeyX => RDevKey::KeyX,
+            KeyboardKey::KeyC => RDevKey::KeyC,
+            KeyboardKey::KeyV => RDevKey::KeyV,
+            KeyboardKey::KeyB => RDevKey::KeyB,
+            KeyboardKey::KeyN => RDevKey::KeyN,
+            KeyboardKey::KeyM => RDevKey::KeyM,
+            KeyboardKey::Comma => RDevKey::Comma,
+            KeyboardKey::Dot => RDevKey::Dot,
+            KeyboardKey::Slash => RDevKey::Slash,
+            KeyboardKey::Insert => RDevKey::Insert,
+            KeyboardKey::KpReturn => RDevKey::KpReturn,
+            KeyboardKey::KpMinus => RDevKey::KpMinus,
+            KeyboardKey::KpPlus => RDevKey::KpPlus,
+            KeyboardKey::KpMultiply => RDevKey::KpMultiply,
+            KeyboardKey::KpDivide => RDevKey::KpDivide,
+            KeyboardKey::Kp0 => RDevKey::Kp0,
+            KeyboardKey::Kp1 => RDevKey::Kp1,
+            KeyboardKey::Kp2 => RDevKey::Kp2,
+            KeyboardKey::Kp3 => RDevKey::Kp3,
+            KeyboardKey::Kp4 => RDevKey::Kp4,
+            KeyboardKey::Kp5 => RDevKey::Kp5,
+            KeyboardKey::Kp6 => RDevKey::Kp6,
+            KeyboardKey::Kp7 => RDevKey::Kp7,
+            KeyboardKey::Kp8 => RDevKey::Kp8,
+            KeyboardKey::Kp9 => RDevKey::Kp9,
+            KeyboardKey::KpDelete => RDevKey::KpDelete,
+            KeyboardKey::Unknown(code) => RDevKey::Unknown(code),
+            // rdev has no name for the rest, they're sent by their code. keys
+            // that don't have one on this platform never get this far, see
+            // `exists`.
+            _ => RDevKey::Unknown(
+                self.code()
+                    .expect("keys that don't exist are rejected by the config"),
+            ),
+        }
+    }
+
+    // whether the key can be pressed and typed on this platform. the keys
+    // rdev has no name for only exist if we know their code, the media keys
+    // on a mac for one don't send key events at all.
+    pub fn exists(self) -> bool {
+        let by_code = matches!(
+            self,
+            KeyboardKey::F13
+                | KeyboardKey::F14
+                | KeyboardKey::F15
+                | KeyboardKey::F16
+                | KeyboardKey::F17
+                | KeyboardKey::F18
+                | KeyboardKey::F19
+                | KeyboardKey::F20
+                | KeyboardKey::F21
+                | KeyboardKey::F22
+                | KeyboardKey::F23
+                | KeyboardKey::F24
+                | KeyboardKey::VolumeUp
+                | KeyboardKey::VolumeDown
+                | KeyboardKey::VolumeMute
+                | KeyboardKey::MediaPlayPause
+                | KeyboardKey::MediaNext
+                | KeyboardKey::MediaPrevious
+                | KeyboardKey::BrightnessUp
+                | KeyboardKey::BrightnessDown
+        );
+        !by_code || self.code().is_some()
+    }
+
+    // `code:123` is a key by the raw code the os reports for it, for keys
+    // that don't have a name. codes differ between platforms (x keycodes on
+    // linux, virtual key codes on macos). a code we have a name for is the
+    // named key.
+    pub fn from_config_name(name: &str) -> Option<Self> {
+        match KeyboardKey::from_config_kebab(name)? {
+            KeyboardKey::Unknown(code) => Some(KeyboardKey::from_code(code)),
+            key => Some(key),
+        }
+    }
+
+    pub fn from_code(code: u32) -> Self {
+        NAMED_CODES
+            .iter()
+            .find(|(_, named_code)| *named_code == code)
+            .map_or(KeyboardKey::Unknown(code), |(key, _)| *key)
+    }
+
+    // the raw code of a key rdev has no name for
+    pub fn code(self) -> Option<u32> {
+        match self {
+            KeyboardKey::Unknown(code) => Some(code),
+            _ => NAMED_CODES
+                .iter()
+                .find(|(key, _)| *key == self)
+                .map(|(_, code)| *code),
+        }
+    }
+}
+
+// the keys rdev only knows as `Unknown(code)`, by the code the os reports
+#[cfg(target_os = "linux")]
+const NAMED_CODES: &[(KeyboardKey, u32)] = &[
+    (KeyboardKey::F13, 191),
+    (KeyboardKey::F14, 192),
+    (KeyboardKey::F15, 193),
+    (KeyboardKey::F16, 194),
+    (KeyboardKey::F17, 195),
+    (KeyboardKey::F18, 196),
+    (KeyboardKey::F19, 197),
+    (KeyboardKey::F20, 198),
+    (KeyboardKey::F21, 199),
+    (KeyboardKey::F22, 200),
+    (KeyboardKey::F23, 201),
+    (KeyboardKey::F24, 202),
+    (KeyboardKey::VolumeMute, 121),
+    (KeyboardKey::VolumeDown, 122),
+    (KeyboardKey::VolumeUp, 123),
+    (KeyboardKey::MediaNext, 171),
+    (KeyboardKey::MediaPlayPause, 172),
+    (KeyboardKey::MediaPrevious, 173),
+    (KeyboardKey::BrightnessDown, 232),
+    (KeyboardKey::BrightnessUp, 233),
+];
+
+// the media keys on a mac keyboard don't send key events at all, only the
+// ones below do
+#[cfg(target_os = "macos")]
+const NAMED_CODES: &[(KeyboardKey, u32)] = &[
+    (KeyboardKey::F13, 105),
+    (KeyboardKey::F14, 107),
+    (KeyboardKey::F15, 113),
+    (KeyboardKey::F16, 106),
+    (KeyboardKey::F17, 64),
+    (KeyboardKey::F18, 79),
+    (KeyboardKey::F19, 80),
+    (KeyboardKey::F20, 90),
+    (KeyboardKey::VolumeUp, 72),
+    (KeyboardKey::VolumeDown, 73),
+    (KeyboardKey::VolumeMute, 74),
+];
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+const NAMED_CODES: &[(KeyboardKey, u32)] = &[];
+
+impl MouseButton {
+    // extra buttons are reported by their raw code which differs between
+    // platforms, `button-4` is whatever the os calls code 4
+    pub fn new_from_rdev(button: RDevButton) -> Self {
+        match button {
+            RDevButton::Left => MouseButton::ButtonLeft,
+            RDevButton::Right => MouseButton::ButtonRight,
+            RDevButton::Middle => MouseButton::ButtonMiddle,
+            RDevButton::Unknown(4) => MouseButton::Button4,
+            RDevButton::Unknown(5) => MouseButton::Button5,
+            RDevButton::Unknown(6) => MouseButton::Button6,
+            RDevButton::Unknown(7) => MouseButton::Button7,
+            RDevButton::Unknown(8) => MouseButton::Button8,
+            RDevButton::Unknown(9) => MouseButton::Button9,
+            RDevButton::Unknown(code) => MouseButton::Unknown(code),
+        }
+    }
+
+    // its bit in a `ButtonSet`
+    fn bit(self) -> usize {
+        match self {
+            MouseButton::Unknown(code) => MouseButton::COUNT as usize - 1 + code as usize,
+            button => button.index() as usize,
+        }
+    }
+
+    fn from_bit(bit: usize) -> Self {
+        match bit.checked_sub(MouseButton::COUNT as usize - 1) {
+            Some(code) => MouseButton::Unknown(code as u8),
+            None => MouseButton::from_index(bit as u32).expect("not a named button"),
+        }
+    }
+
+    pub fn to_rdev(self) -> RDevButton {
+        match self {
+            MouseButton::ButtonLeft => RDevButton::Left,
+            MouseButton::ButtonRight => RDevButton::Right,
+            MouseButton::ButtonMiddle => RDevButton::Middle,
+            MouseButton::Button4 => RDevButton::Unknown(4),
+            MouseButton::Button5 => RDevButton::Unknown(5),
+            MouseButton::Button6 => RDevButton::Unknown(6),
+            MouseButton::Button7 => RDevButton::Unknown(7),
+            MouseButton::Button8 => RDevButton::Unknown(8),
+            MouseButton::Button9 => RDevButton::Unknown(9),
+            MouseButton::Unknown(code) => RDevButton::Unknown(code),
+        }
+    }
+}
+
+// keys and buttons are named by their kebab case names in the config
+// (`meta-left`, `key-k`, `button-middle`)
+macro_rules! deserialize_config_kebab {
+    ($($name:ident => $what:literal),*) => {
+        $(
+            impl<'de> Deserialize<'de> for $name {
+                fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                    let name = String::deserialize(deserializer)?;
+                    $name::from_config_kebab(&name)
+                        .ok_or_else(|| D::Error::custom(format!("unknown {} `{}`", $what, name)))
+                }
+            }
+        )*
+    };
+}
+
+deserialize_config_kebab!(ModifierKey => "modifier", ModifierAlias => "modifier", MouseButton => "mouse button");
+
+impl<'de> Deserialize<'de> for KeyboardKey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        KeyboardKey::from_config_name(&name)
+            .ok_or_else(|| D::Error::custom(format!("unknown key `{}`", name)))
     }
 }
 
@@ -129,7 +901,9 @@ pub enum ModifierKey {
     ShiftRight,
     MetaRight,
     MetaLeft,
+    #[config(alias = "ctrl-left")]
     ControlLeft,
+    #[config(alias = "ctrl-right")]
     ControlRight,
     AltGr,
     Alt,
@@ -137,6 +911,16 @@ pub enum ModifierKey {
     CapsLock,
 }
 
+// a modifier on either side of the keyboard. `any-alt` is `alt` or `alt-gr`,
+// plain `alt` is just the left one like it always was.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIndex, PartialOrd, Ord, Hash, ConfigKebabCase)]
+pub enum ModifierAlias {
+    Shift,
+    Ctrl,
+    Meta,
+    AnyAlt,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIndex, PartialOrd, Ord, Hash, ConfigKebabCase)]
 pub enum KeyboardKey {
     Backspace,
@@ -234,5 +1018,203 @@ pub enum KeyboardKey {
     Kp8,
     Kp9,
     KpDelete,
+    F13,
+    F14,
+    F15,
+    F16,
+    F17,
+    F18,
+    F19,
+    F20,
+    F21,
+    F22,
+    F23,
+    F24,
+    VolumeUp,
+    VolumeDown,
+    VolumeMute,
+    MediaPlayPause,
+    MediaNext,
+    MediaPrevious,
+    BrightnessUp,
+    BrightnessDown,
+    // keys without a name, by their raw code
+    #[config(rename = "code")]
     Unknown(u32),
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIndex, PartialOrd, Ord, Hash, ConfigKebabCase)]
+pub enum MouseButton {
+    ButtonLeft,
+    ButtonRight,
+    ButtonMiddle,
+    Button4,
+    Button5,
+    Button6,
+    Button7,
+    Button8,
+    Button9,
+    Unknown(u8),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        ButtonSet, HeldButtons, HeldModifiers, KeyboardKey, ModifierAlias, ModifierKey,
+        ModifierSet, Modifiers, MouseButton, Trigger,
+    };
+
+    #[test]
+    fn modifier_sets_behave_like_sets() {
+        let held = HeldModifiers::default();
+        held.press(ModifierKey::MetaLeft);
+        held.press(ModifierKey::ShiftLeft);
+        held.press(ModifierKey::CapsLock);
+        held.release(ModifierKey::CapsLock);
+
+        let mut modifiers = held.get();
+        assert_eq!(
+            modifiers.iter().collect::<Vec<_>>(),
+            vec![ModifierKey::ShiftLeft, ModifierKey::MetaLeft]
+        );
+        assert!(!modifiers.insert(ModifierKey::MetaLeft));
+        assert!(modifiers.remove(ModifierKey::MetaLeft));
+        assert!(!modifiers.contains(ModifierKey::MetaLeft));
+        assert_eq!(
+            held.get().difference(modifiers),
+            [ModifierKey::MetaLeft].into_iter().collect()
+        );
+    }
+
+    #[test]
+    fn button_sets_cover_every_code() {
+        let held = HeldButtons::default();
+        held.press(MouseButton::Button4);
+        held.press(MouseButton::Unknown(255));
+        held.press(MouseButton::ButtonLeft);
+        held.press(MouseButton::Unknown(0));
+        held.release(MouseButton::Unknown(0));
+
+        let mut buttons = held.get();
+        assert_eq!(
+            buttons.iter().collect::<Vec<_>>(),
+            vec![
+                MouseButton::ButtonLeft,
+                MouseButton::Button4,
+                MouseButton::Unknown(255)
+            ]
+        );
+        assert!(!buttons.insert(MouseButton::Unknown(255)));
+        assert!(buttons.remove(MouseButton::Unknown(255)));
+        assert!(!buttons.contains(MouseButton::Unknown(255)));
+        assert_ne!(buttons, held.get());
+        assert_eq!(ButtonSet::new().iter().count(), 0);
+    }
+
+    #[test]
+    fn aliases_match_either_side() {
+        let set = |keys: &[ModifierKey]| keys.iter().copied().collect::<ModifierSet<_>>();
+        let modifiers = |exact: &[ModifierKey], aliases: &[ModifierAlias]| Modifiers {
+            exact: set(exact),
+            aliases: aliases.iter().copied().collect(),
+        };
+        let shift_meta = set(&[ModifierKey::ShiftLeft, ModifierKey::MetaLeft]);
+
+        assert!(
+            modifiers(&[ModifierKey::ShiftLeft, ModifierKey::MetaLeft], &[]).matches(shift_meta)
+        );
+        assert!(modifiers(&[ModifierKey::MetaLeft], &[ModifierAlias::Shift]).matches(shift_meta));
+        assert!(modifiers(&[], &[ModifierAlias::Shift, ModifierAlias::Meta]).matches(shift_meta));
+        // everything held has to be part of the bind
+        assert!(!modifiers(&[], &[ModifierAlias::Shift]).matches(shift_meta));
+        assert!(!modifiers(&[ModifierKey::MetaLeft], &[ModifierAlias::Ctrl]).matches(shift_meta));
+
+        // both sides held at once are covered by one alias, or one side named
+        // exactly and the alias for the other
+        let both = set(&[ModifierKey::ShiftLeft, ModifierKey::ShiftRight]);
+        assert!(modifiers(&[], &[ModifierAlias::Shift]).matches(both));
+        assert!(modifiers(&[ModifierKey::ShiftLeft], &[ModifierAlias::Shift]).matches(both));
+        assert!(
+            !modifiers(&[ModifierKey::ShiftLeft], &[ModifierAlias::Shift])
+                .matches(set(&[ModifierKey::ShiftLeft]))
+        );
+
+        assert!(modifiers(&[], &[ModifierAlias::AnyAlt]).matches(set(&[ModifierKey::AltGr])));
+        assert_eq!("alt".parse(), Ok(ModifierKey::Alt));
+        assert_eq!("any-alt".parse(), Ok(ModifierAlias::AnyAlt));
+    }
+
+    #[test]
+    fn names_round_trip() {
+        fn round_trip<T>(variants: &[T])
+        where
+            T: std::fmt::Display + std::str::FromStr<Err = String> + PartialEq + std::fmt::Debug,
+        {
+            for variant in variants {
+                assert_eq!(variant.to_string().parse::<T>().as_ref(), Ok(variant));
+            }
+        }
+        round_trip(ModifierKey::ALL_VARIANTS);
+        round_trip(ModifierAlias::ALL_VARIANTS);
+        round_trip(KeyboardKey::ALL_VARIANTS);
+        round_trip(MouseButton::ALL_VARIANTS);
+        round_trip(&[KeyboardKey::Unknown(250)]);
+        round_trip(&[MouseButton::Unknown(12)]);
+
+        assert_eq!(KeyboardKey::BackQuote.to_string(), "back-quote");
+        assert_eq!(KeyboardKey::F10.to_string(), "f-10");
+        assert_eq!(
+            Trigger::Keyboard(KeyboardKey::Unknown(250)).to_string(),
+            "code:250"
+        );
+        assert_eq!("ctrl-left".parse(), Ok(ModifierKey::ControlLeft));
+        assert_eq!(ModifierKey::ControlLeft.to_string(), "control-left");
+        assert_eq!(
+            "key-ü".parse::<KeyboardKey>(),
+            Err("unknown KeyboardKey `key-ü`".to_string())
+        );
+    }
+
+    #[test]
+    fn raw_codes_round_trip() {
+        assert_eq!(
+            KeyboardKey::from_config_name("code:250"),
+            Some(KeyboardKey::Unknown(250))
+        );
+        assert_eq!(KeyboardKey::Unknown(250).code(), Some(250));
+        assert_eq!(KeyboardKey::from_config_name("code:x"), None);
+        assert_eq!(
+            KeyboardKey::from_config_kebab("code:250"),
+            Some(KeyboardKey::Unknown(250))
+        );
+        assert_eq!(
+            MouseButton::from_config_kebab("unknown:12"),
+            Some(MouseButton::Unknown(12))
+        );
+        assert_eq!(MouseButton::from_config_kebab("unknown"), None);
+
+        // named keys without an rdev name go through their code as well
+        for key in [KeyboardKey::F13, KeyboardKey::F20, KeyboardKey::VolumeUp] {
+            let code = key.code().expect("has a code");
+            assert_eq!(KeyboardKey::from_code(code), key);
+        }
+        assert_eq!(
+            KeyboardKey::from_config_kebab("media-play-pause"),
+            Some(KeyboardKey::MediaPlayPause)
+        );
+    }
+
+    #[test]
+    fn keys_that_exist_can_be_sent() {
+        for key in KeyboardKey::ALL_VARIANTS {
+            if key.exists() {
+                key.to_rdev();
+            }
+        }
+        #[cfg(target_os = "linux")]
+        assert!(KeyboardKey::MediaPlayPause.exists());
+        // mac media keys don't send key events
+        #[cfg(target_os = "macos")]
+        assert!(!KeyboardKey::MediaPlayPause.exists());
+    }
+}
@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+
+use rdev::{EventType, Keyboard, KeyboardState};
+
+use crate::key::{KeyStroke, KeyboardKey, ModifierKey};
+
+// turns the symbols of `symbol:` binds into the physical keys that type them
+// on the current layout
+pub trait Keymap: Send + Sync {
+    // the key that types `symbol` and whether shift has to be held for it
+    fn key_for_symbol(&self, symbol: char) -> Option<(KeyboardKey, bool)>;
+}
+
+// a us layout, used in tests and when the real layout can't be read
+pub struct UsKeymap;
+
+impl Keymap for UsKeymap {
+    fn key_for_symbol(&self, symbol: char) -> Option<(KeyboardKey, bool)> {
+        let stroke = KeyStroke::from_char(symbol)?;
+        Some((
+            stroke.key,
+            stroke.modifiers.contains(&ModifierKey::ShiftLeft),
+        ))
+    }
+}
+
+// the keys that type something on a typical layout. every one of them is
+// looked up once when the keymap is created.
+const TYPING_KEYS: &[KeyboardKey] = &[
+    KeyboardKey::BackQuote,
+    KeyboardKey::Num1,
+    KeyboardKey::Num2,
+    KeyboardKey::Num3,
+    KeyboardKey::Num4,
+    KeyboardKey::Num5,
+    KeyboardKey::Num6,
+    KeyboardKey::Num7,
+    KeyboardKey::Num8,
+    KeyboardKey::Num9,
+    KeyboardKey::Num0,
+    KeyboardKey::Minus,
+    KeyboardKey::Equal,
+    KeyboardKey::KeyQ,
+    KeyboardKey::KeyW,
+    KeyboardKey::KeyE,
+    KeyboardKey::KeyR,
+    KeyboardKey::KeyT,
+    KeyboardKey::KeyY,
+    KeyboardKey::KeyU,
+    KeyboardKey::KeyI,
+    KeyboardKey::KeyO,
+    KeyboardKey::KeyP,
+    KeyboardKey::LeftBracket,
+    KeyboardKey::RightBracket,
+    KeyboardKey::KeyA,
+    KeyboardKey::KeyS,
+    KeyboardKey::KeyD,
+    KeyboardKey::KeyF,
+    KeyboardKey::KeyG,
+    KeyboardKey::KeyH,
+    KeyboardKey::KeyJ,
+    KeyboardKey::KeyK,
+    KeyboardKey::KeyL,
+    KeyboardKey::SemiColon,
+    KeyboardKey::Quote,
+    KeyboardKey::BackSlash,
+    KeyboardKey::IntlBackslash,
+    KeyboardKey::KeyZ,
+    KeyboardKey::KeyX,
+    KeyboardKey::KeyC,
+    KeyboardKey::KeyV,
+    KeyboardKey::KeyB,
+    KeyboardKey::KeyN,
+    KeyboardKey::KeyM,
+    KeyboardKey::Comma,
+    KeyboardKey::Dot,
+    KeyboardKey::Slash,
+];
+
+// the layout the os is using. rdev's keyboard translates keys the same way
+// the os would (through xkb on linux) so we type every key once without and
+// once with shift and remember what came out.
+pub struct SystemKeymap {
+    symbols: HashMap<char, (KeyboardKey, bool)>,
+}
+
+impl SystemKeymap {
+    pub fn new() -> Option<Self> {
+        let mut keyboard = Keyboard::new()?;
+        let mut symbols = HashMap::new();
+        // unshifted first so a symbol that both can type doesn't need shift
+        for shift in [false, true] {
+            for key in TYPING_KEYS {
+                keyboard.reset();
+                if shift {
+                    keyboard.add(&EventType::KeyPress(ModifierKey::ShiftLeft.to_rdev()));
+                }
+                let typed = keyboard.add(&EventType::KeyPress(key.to_rdev()));
+                let mut chars = typed.iter().flat_map(|typed| typed.chars());
+                if let (Some(symbol), None) = (chars.next(), chars.next()) {
+                    symbols.entry(symbol).or_insert((*key, shift));
+                }
+            }
+        }
+        keyboard.reset();
+        Some(Self { symbols })
+    }
+}
+
+impl Keymap for SystemKeymap {
+    fn key_for_symbol(&self, symbol: char) -> Option<(KeyboardKey, bool)> {
+        self.symbols.get(&symbol).copied()
+    }
+}
+
+pub fn default_keymap() -> Box<dyn Keymap> {
+    match SystemKeymap::new() {
+        Some(keymap) => Box::new(keymap),
+        None => {
+            eprintln!("error reading the keyboard layout, `symbol:` binds assume a us layout");
+            Box::new(UsKeymap)
+        }
+    }
+}
@@ -0,0 +1,17 @@
+// everything but the command line lives here so the benches can use the
+// same code the daemon runs
+pub mod action;
+pub mod app;
+pub mod config;
+pub mod config_manager;
+pub mod control;
+pub mod executor;
+pub mod handler;
+pub mod key;
+pub mod keymap;
+pub mod mouse;
+pub mod record;
+pub mod scroll;
+pub mod sequence;
+pub mod simulate;
+pub mod utils;
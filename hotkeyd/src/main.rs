@@ -1,74 +1,92 @@
-use std::{
-    collections::HashSet,
-    fs,
-    ops::Deref,
-    sync::{Arc, RwLock},
-};
+use std::{fs, ops::Deref, process};
 
-use clap::{Parser, ValueEnum};
+use clap::{Parser, Subcommand};
 
-use config_manager::CONFIG_MANAGER;
-use key::{Key, ModifierKey};
-use rdev::{grab, Event, EventType};
+use hotkeyd::{
+    config::Config, config_manager::CONFIG_MANAGER, control, handler::Handler,
+    keymap::default_keymap, record, simulate::RdevSink, utils::get_user,
+};
+use rdev::{grab, Event};
 use serde::Serialize;
-use utils::get_user;
-
-mod config_manager;
-mod key;
-mod utils;
 
 async fn hotkeyd() {
-    let _ = CONFIG_MANAGER.deref();
-    let held_modifiers: Arc<RwLock<HashSet<ModifierKey>>> = Arc::new(RwLock::new(HashSet::new()));
-    grab(move |event: Event| -> Option<Event> {
-        match event.event_type {
-            EventType::KeyPress(key) => match Key::new_from_rdev(key) {
-                Key::Modifier(modifier_key) => {
-                    held_modifiers
-                        .write()
-                        .expect("held_modifiers was poisoned")
-                        .insert(modifier_key);
-                }
-                Key::Keyboard(key) => {
-                    if CONFIG_MANAGER
-                        .exec(&*held_modifiers.read().expect("poisoned"), &key)
-                        .is_none()
-                    {
-                        return Some(event);
-                    } else {
-                        return None;
+    // the control socket needs the handler too and both live as long as the
+    // daemon does
+    let handler: &'static Handler =
+        Box::leak(Box::new(Handler::new(CONFIG_MANAGER.deref(), &RdevSink)));
+    tokio::spawn(control::serve(handler));
+    tokio::spawn(control::pause_on_signal(handler));
+    grab(move |event: Event| -> Option<Event> { handler.handle(event) }).expect("fuck");
+}
+
+// parses the config in `path` the same way the daemon would. every error is
+// printed as `path:line:column: message`, otherwise the binds are listed the
+// way they were understood. returns whether the config is valid.
+fn check(path: &str) -> bool {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(err) => {
+            eprintln!("{}: {}", path, err);
+            return false;
+        }
+    };
+
+    let config = match Config::new_from_str(&content, default_keymap().as_ref()) {
+        Ok(config) => config,
+        Err(errors) => {
+            for error in errors.iter() {
+                match error.location {
+                    Some((line, column)) => {
+                        eprintln!("{}:{}:{}: {}", path, line, column, error.message)
                     }
+                    None => eprintln!("{}: {}", path, error.message),
                 }
-            },
-            EventType::KeyRelease(key) => match Key::new_from_rdev(key) {
-                Key::Modifier(modifier_key) => {
-                    held_modifiers
-                        .write()
-                        .expect("held_modifiers was poisoned")
-                        .remove(&modifier_key);
-                }
-                Key::Keyboard(_) => {}
-            },
-            _ => {}
+            }
+            eprintln!("{} error(s) in {}", errors.len(), path);
+            return false;
         }
-        return Some(event);
-    })
-    .expect("fuck");
+    };
+
+    let binds = config.bind_names();
+    for bind in binds.iter() {
+        println!("{}", bind);
+    }
+    println!("{} is valid ({} binds)", path, binds.len());
+    true
 }
 
-#[derive(ValueEnum, Clone, Debug, Serialize)]
+#[derive(Subcommand, Clone, Debug, Serialize)]
 pub enum Command {
     #[serde(rename = "install")]
     Install,
 
     #[serde(rename = "daemon")]
     Daemon,
+
+    /// validates a config file without starting the daemon
+    #[serde(rename = "check")]
+    Check { path: String },
+
+    /// talks to the running daemon, e.g. `ctl trigger meta-left + key-k`
+    #[serde(rename = "ctl")]
+    Ctl { method: String, args: Vec<String> },
+
+    /// prints the bind for every chord that is typed, to paste into the config
+    #[serde(rename = "record")]
+    Record,
+
+    /// lists the commands the daemon ran recently along with their output
+    #[serde(rename = "history")]
+    History {
+        #[arg(short = 'n', long, default_value_t = 20)]
+        limit: usize,
+    },
 }
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Args {
-    #[clap(value_enum)]
+    #[command(subcommand)]
     pub command: Command,
 }
 
@@ -78,6 +96,26 @@ async fn main() {
 
     match args.command {
         Command::Daemon => hotkeyd().await,
+        Command::Check { path } => {
+            if !check(&path) {
+                process::exit(1);
+            }
+        }
+        Command::Ctl { method, args } => {
+            if !control::ctl(&method, &args) {
+                process::exit(1);
+            }
+        }
+        Command::Record => {
+            if !record::record() {
+                process::exit(1);
+            }
+        }
+        Command::History { limit } => {
+            if !control::history(limit) {
+                process::exit(1);
+            }
+        }
         Command::Install => {
             let user = get_user().expect("couldn't get user");
             let path = format!("/Users/{}/Library/LaunchAgents/hotkeyd.plist", user);
@@ -0,0 +1,127 @@
+use std::time::{Duration, Instant};
+
+use rdev::display_size;
+use serde::Deserialize;
+use toml::Value;
+
+use crate::{
+    action::Action,
+    config::{deserialize_entry, split_table, EntryError},
+};
+
+// spawning a shell for every pixel the cursor moves would be way too much so
+// mouse macros run at most once per `rate_limit`
+const DEFAULT_RATE_LIMIT: Duration = Duration::from_millis(50);
+
+// runs an action when the mouse moves while a set of modifiers is held
+#[derive(Debug, Clone)]
+pub struct MouseMacro {
+    pub action: Action,
+    pub rate_limit: Duration,
+    // keep the cursor where it is while the macro is active
+    pub block: bool,
+}
+
+// the settings of a mouse macro that aren't part of its action
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MouseSettings {
+    rate_limit_ms: Option<u64>,
+    #[serde(default)]
+    block: bool,
+}
+
+impl MouseMacro {
+    pub fn from_config(mut value: Value) -> Result<Self, EntryError> {
+        let settings: MouseSettings =
+            deserialize_entry(split_table(&mut value, &["rate_limit_ms", "block"]))?;
+        Ok(Self {
+            action: Action::from_config(value)?,
+            rate_limit: settings
+                .rate_limit_ms
+                .map_or(DEFAULT_RATE_LIMIT, Duration::from_millis),
+            block: settings.block,
+        })
+    }
+}
+
+// where the cursor is and how far it moved since a mouse macro last ran
+pub struct Motion {
+    pub x: f64,
+    pub y: f64,
+    pub dx: f64,
+    pub dy: f64,
+}
+
+impl Motion {
+    // the axis that moved the most wins. y grows downward.
+    pub fn direction(&self) -> &'static str {
+        if self.dx == 0.0 && self.dy == 0.0 {
+            "none"
+        } else if self.dx.abs() >= self.dy.abs() {
+            if self.dx > 0.0 {
+                "right"
+            } else {
+                "left"
+            }
+        } else if self.dy > 0.0 {
+            "down"
+        } else {
+            "up"
+        }
+    }
+
+    pub fn envs(&self) -> Vec<(&'static str, String)> {
+        let mut envs = vec![
+            ("HOTKEYD_X", self.x.to_string()),
+            ("HOTKEYD_Y", self.y.to_string()),
+            ("HOTKEYD_DX", self.dx.to_string()),
+            ("HOTKEYD_DY", self.dy.to_string()),
+            ("HOTKEYD_DIRECTION", self.direction().to_string()),
+        ];
+        if let Ok((width, height)) = display_size() {
+            envs.push(("HOTKEYD_SCREEN_WIDTH", width.to_string()));
+            envs.push(("HOTKEYD_SCREEN_HEIGHT", height.to_string()));
+        }
+        envs
+    }
+}
+
+#[derive(Default)]
+pub struct MouseTracker {
+    position: Option<(f64, f64)>,
+
+    // where the cursor was the last time a mouse macro ran. the deltas are
+    // measured from here so moves that were skipped because of the rate
+    // limit still count.
+    anchor: Option<(f64, f64)>,
+    last_run: Option<Instant>,
+}
+
+impl MouseTracker {
+    // records a move to `x`, `y`. `rate_limit` is set if a mouse macro is
+    // active, in which case the motion is returned if the macro should run.
+    pub fn moved(&mut self, x: f64, y: f64, rate_limit: Option<Duration>) -> Option<Motion> {
+        let previous = self.position.replace((x, y));
+        let Some(rate_limit) = rate_limit else {
+            self.anchor = None;
+            return None;
+        };
+
+        let anchor = *self.anchor.get_or_insert(previous.unwrap_or((x, y)));
+        if let Some(last_run) = self.last_run {
+            if last_run.elapsed() < rate_limit {
+                return None;
+            }
+        }
+
+        self.last_run = Some(Instant::now());
+        self.anchor = Some((x, y));
+        Some(Motion {
+            x,
+            y,
+            dx: x - anchor.0,
+            dy: y - anchor.1,
+        })
+    }
+}
@@ -0,0 +1,131 @@
+use rdev::{listen, Event, EventType};
+
+use crate::{
+    config::Bind,
+    key::{HeldButtons, HeldModifiers, Key, MouseButton, Trigger},
+};
+
+// turns what is pressed into the bind that would match it. modifiers and
+// buttons are tracked with what the daemon uses and the chord is the `Bind`
+// it would look up.
+#[derive(Default)]
+pub struct Recorder {
+    held_modifiers: HeldModifiers,
+    held_buttons: HeldButtons,
+
+    // holding a key down repeats its press, it's only printed once
+    held_key: Option<Trigger>,
+}
+
+impl Recorder {
+    // returns the chord if the event completes one
+    pub fn event(&mut self, event_type: &EventType) -> Option<String> {
+        match *event_type {
+            EventType::KeyPress(key) => match Key::new_from_rdev(key) {
+                Key::Modifier(modifier_key) => {
+                    self.held_modifiers.press(modifier_key);
+                    None
+                }
+                Key::Keyboard(key) => self.press(Trigger::Keyboard(key)),
+            },
+            EventType::KeyRelease(key) => {
+                match Key::new_from_rdev(key) {
+                    Key::Modifier(modifier_key) => {
+                        self.held_modifiers.release(modifier_key);
+                    }
+                    Key::Keyboard(key) => self.release(Trigger::Keyboard(key)),
+                }
+                None
+            }
+            EventType::ButtonPress(button) => {
+                let button = MouseButton::new_from_rdev(button);
+                let chord = self.press(Trigger::Button(button));
+                // a button is never its own modifier
+                self.held_buttons.press(button);
+                chord
+            }
+            EventType::ButtonRelease(button) => {
+                let button = MouseButton::new_from_rdev(button);
+                self.held_buttons.release(button);
+                self.release(Trigger::Button(button));
+                None
+            }
+            _ => None,
+        }
+    }
+
+    fn press(&mut self, key: Trigger) -> Option<String> {
+        if self.held_key == Some(key) {
+            return None;
+        }
+        self.held_key = Some(key);
+
+        let chord = Bind::new(self.held_modifiers.get(), self.held_buttons.get(), key);
+        Some(chord.to_string())
+    }
+
+    fn release(&mut self, key: Trigger) {
+        if self.held_key == Some(key) {
+            self.held_key = None;
+        }
+    }
+}
+
+// prints every chord that is typed until killed. nothing is blocked, the
+// keys still reach whatever is focused.
+pub fn record() -> bool {
+    println!("press the keys to bind, ctrl-c to stop");
+    let mut recorder = Recorder::default();
+    let result = listen(move |event: Event| {
+        if let Some(chord) = recorder.event(&event.event_type) {
+            println!("{}", chord);
+        }
+    });
+    match result {
+        Ok(()) => true,
+        Err(err) => {
+            eprintln!("error listening for keys: {:?}", err);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rdev::{Button, EventType, Key as RDevKey};
+
+    use super::Recorder;
+
+    #[test]
+    fn chords_are_printed_as_binds() {
+        let mut recorder = Recorder::default();
+        let events = [
+            EventType::KeyPress(RDevKey::MetaLeft),
+            EventType::KeyPress(RDevKey::BackQuote),
+            // a repeat from holding the key down
+            EventType::KeyPress(RDevKey::BackQuote),
+            EventType::KeyRelease(RDevKey::BackQuote),
+            EventType::KeyRelease(RDevKey::MetaLeft),
+            EventType::ButtonPress(Button::Unknown(4)),
+            EventType::KeyPress(RDevKey::Unknown(250)),
+            EventType::KeyRelease(RDevKey::Unknown(250)),
+            EventType::ButtonRelease(Button::Unknown(4)),
+            EventType::KeyPress(RDevKey::ControlLeft),
+            EventType::ButtonPress(Button::Middle),
+        ];
+        let chords: Vec<String> = events
+            .iter()
+            .filter_map(|event| recorder.event(event))
+            .collect();
+        assert_eq!(
+            chords,
+            vec![
+                "meta-left + back-quote",
+                // a button can fire a bind by itself
+                "button-4",
+                "button-4 + code:250",
+                "control-left + button-middle",
+            ]
+        );
+    }
+}
@@ -0,0 +1,116 @@
+use serde::Deserialize;
+use toml::Value;
+
+use crate::{
+    action::Action,
+    config::{deserialize_entry, EntryError},
+};
+
+// a transform as it is written in the config. `multiply` and `invert` apply
+// to both axes, the `_x`/`_y` versions to just one.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawScrollTransform {
+    multiply: Option<f64>,
+    multiply_x: Option<f64>,
+    multiply_y: Option<f64>,
+    #[serde(default)]
+    invert: bool,
+    #[serde(default)]
+    invert_x: bool,
+    #[serde(default)]
+    invert_y: bool,
+    #[serde(default)]
+    swap: bool,
+}
+
+// rescales the deltas of a scroll. the deltas are scaled first and swapped
+// after.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(from = "RawScrollTransform")]
+pub struct ScrollTransform {
+    multiply_x: f64,
+    multiply_y: f64,
+    swap: bool,
+}
+
+impl ScrollTransform {
+    // the deltas that should be sent instead of `delta_x` and `delta_y`, none
+    // if they don't add up to a whole step yet
+    pub fn apply(
+        &self,
+        delta_x: i64,
+        delta_y: i64,
+        tracker: &mut ScrollTracker,
+    ) -> Option<(i64, i64)> {
+        let x = tracker.add(0, delta_x as f64 * self.multiply_x);
+        let y = tracker.add(1, delta_y as f64 * self.multiply_y);
+        match (x, y) {
+            (0, 0) => None,
+            (x, y) if self.swap => Some((y, x)),
+            (x, y) => Some((x, y)),
+        }
+    }
+}
+
+// wheels scroll in whole steps so a delta scaled below one would be lost on
+// every event. what is left over after the whole steps are sent is kept for
+// the next scroll, per axis.
+#[derive(Default)]
+pub struct ScrollTracker {
+    remainder: [f64; 2],
+}
+
+impl ScrollTracker {
+    // the whole steps of `delta` plus what was left over on `axis`
+    fn add(&mut self, axis: usize, delta: f64) -> i64 {
+        let total = self.remainder[axis] + delta;
+        let steps = total.trunc();
+        self.remainder[axis] = total - steps;
+        steps as i64
+    }
+
+    // a scroll that isn't transformed starts over
+    pub fn reset(&mut self) {
+        self.remainder = [0.0; 2];
+    }
+}
+
+impl From<RawScrollTransform> for ScrollTransform {
+    fn from(raw: RawScrollTransform) -> Self {
+        let multiply = raw.multiply.unwrap_or(1.0);
+        let mut multiply_x = raw.multiply_x.unwrap_or(multiply);
+        let mut multiply_y = raw.multiply_y.unwrap_or(multiply);
+        if raw.invert || raw.invert_x {
+            multiply_x = -multiply_x;
+        }
+        if raw.invert || raw.invert_y {
+            multiply_y = -multiply_y;
+        }
+
+        Self {
+            multiply_x,
+            multiply_y,
+            swap: raw.swap,
+        }
+    }
+}
+
+// what happens to a scroll event while a set of modifiers is held
+#[derive(Debug, Clone)]
+pub enum ScrollMacro {
+    // send the scroll on with different deltas
+    Transform(ScrollTransform),
+    // swallow the scroll and run an action with the deltas in its environment
+    Action(Action),
+}
+
+impl ScrollMacro {
+    // a table with a `type` is an action, anything else is a transform
+    pub fn from_config(value: Value) -> Result<Self, EntryError> {
+        if value.get("type").is_some() {
+            return Action::from_config(value).map(ScrollMacro::Action);
+        }
+        deserialize_entry(value).map(ScrollMacro::Transform)
+    }
+}
@@ -0,0 +1,169 @@
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use rdev::EventType;
+
+use crate::{
+    config::Bind,
+    config_manager::{ConfigManager, Lookup},
+    key::{HeldButtons, HeldModifiers, Trigger},
+    simulate::EventSink,
+};
+
+// tracks partially typed sequence binds. while a prefix of a sequence is
+// pending the keys that make it up are swallowed. if the sequence is
+// abandoned (a chord that doesn't continue it or the timeout) the swallowed
+// chords are replayed so nothing the user typed gets lost.
+pub struct SequenceTracker {
+    // a new chord is pushed here to be looked up along with what is already
+    // pending. it is cleared rather than replaced so after the first few
+    // presses looking up a chord doesn't allocate.
+    pending: Vec<Bind>,
+
+    // the last swallowed key while it is still held down. holding a key
+    // down sends `KeyPress` over and over so we need this to tell a repeat
+    // apart from the key being pressed again.
+    held_key: Option<Trigger>,
+
+    // bumped every time `pending` changes so a timeout that was started for
+    // an older prefix doesn't abandon a newer one
+    generation: u64,
+
+    // keys a mode swallowed, their release has to be swallowed too or the
+    // app would see a release without a press
+    captured: HashSet<Trigger>,
+
+    held_modifiers: Arc<HeldModifiers>,
+    held_buttons: Arc<HeldButtons>,
+
+    config_manager: &'static ConfigManager,
+    sink: &'static dyn EventSink,
+}
+
+impl SequenceTracker {
+    pub fn new(
+        held_modifiers: Arc<HeldModifiers>,
+        held_buttons: Arc<HeldButtons>,
+        config_manager: &'static ConfigManager,
+        sink: &'static dyn EventSink,
+    ) -> Self {
+        Self {
+            pending: Vec::new(),
+            held_key: None,
+            generation: 0,
+            captured: HashSet::new(),
+            held_modifiers,
+            held_buttons,
+            config_manager,
+            sink,
+        }
+    }
+
+    // returns true if the key press should be blocked
+    pub fn press(tracker: &Arc<Mutex<Self>>, key: Trigger) -> bool {
+        let mut this = tracker.lock().expect("sequence tracker was poisoned");
+        if !this.pending.is_empty() && this.held_key == Some(key) {
+            return true;
+        }
+
+        let bind = Bind::new(this.held_modifiers.get(), this.held_buttons.get(), key);
+        this.pending.push(bind);
+
+        match this.config_manager.exec(&this.pending, this.sink) {
+            Lookup::Executed => {
+                this.reset();
+                true
+            }
+            // a mode that captures unbound keys throws away whatever was
+            // pending as well
+            Lookup::Captured => {
+                this.reset();
+                this.captured.insert(key);
+                true
+            }
+            Lookup::Pending => {
+                this.held_key = Some(key);
+                this.generation += 1;
+                let timeout = this.config_manager.sequence_timeout();
+                Self::start_timeout(tracker, this.generation, timeout);
+                true
+            }
+            Lookup::NoMatch => {
+                this.pending.pop();
+                if this.pending.is_empty() {
+                    return false;
+                }
+                // the new chord doesn't continue the sequence. give back what
+                // we swallowed and then treat the chord as if it was the first
+                this.abandon();
+                drop(this);
+                Self::press(tracker, key)
+            }
+        }
+    }
+
+    // returns true if the key release should be blocked. the os never saw
+    // the press of a swallowed key so it shouldn't see the release either.
+    pub fn release(tracker: &Arc<Mutex<Self>>, key: Trigger) -> bool {
+        let mut this = tracker.lock().expect("sequence tracker was poisoned");
+        if this.captured.remove(&key) {
+            return true;
+        }
+        if this.held_key != Some(key) {
+            return false;
+        }
+        this.held_key = None;
+        !this.pending.is_empty()
+    }
+
+    fn start_timeout(tracker: &Arc<Mutex<Self>>, generation: u64, timeout: Duration) {
+        let tracker = tracker.clone();
+        thread::spawn(move || {
+            thread::sleep(timeout);
+            let mut this = tracker.lock().expect("sequence tracker was poisoned");
+            if this.generation == generation && !this.pending.is_empty() {
+                this.abandon();
+            }
+        });
+    }
+
+    fn reset(&mut self) {
+        self.pending.clear();
+        self.held_key = None;
+        self.generation += 1;
+    }
+
+    // replays the swallowed chords. the modifiers the user is holding right
+    // now might not be the ones that were held when the chord was typed so we
+    // release/press them around each chord to reproduce it exactly. held
+    // mouse buttons are left alone, pressing them again would click.
+    fn abandon(&mut self) {
+        let held = self.held_modifiers.get();
+        for bind in self.pending.iter() {
+            let extra = held.difference(bind.modifiers());
+            let missing = bind.modifiers().difference(held);
+
+            for modifier in extra.iter() {
+                self.sink.send(EventType::KeyRelease(modifier.to_rdev()));
+            }
+            for modifier in missing.iter() {
+                self.sink.send(EventType::KeyPress(modifier.to_rdev()));
+            }
+
+            self.sink.send(bind.key().press());
+            self.sink.send(bind.key().release());
+
+            for modifier in missing.iter() {
+                self.sink.send(EventType::KeyRelease(modifier.to_rdev()));
+            }
+            for modifier in extra.iter() {
+                self.sink.send(EventType::KeyPress(modifier.to_rdev()));
+            }
+        }
+        self.reset();
+    }
+}
@@ -0,0 +1,93 @@
+use std::{
+    collections::VecDeque,
+    sync::Mutex,
+    thread,
+    time::{Duration, Instant},
+};
+
+use lazy_static::lazy_static;
+use rdev::{simulate, EventType};
+
+// events that we send ourselves come back through the grab callback (at least
+// on macos). we remember what we sent so the callback can let them through
+// untouched instead of treating them as something the user typed. entries
+// expire so that a platform that never echoes them back doesn't end up
+// swallowing real input later on. the echoes only come back once the callback
+// that sent them returns, which takes a while for a long `keys` action, so the
+// time only starts counting then (see `settle`).
+const INJECTED_TTL: Duration = Duration::from_millis(250);
+
+// macos drops synthetic events if they are sent too close together
+const SEND_DELAY: Duration = Duration::from_millis(10);
+
+lazy_static! {
+    // when the callback that sent them returned, `None` until it has
+    static ref INJECTED: Mutex<VecDeque<(EventType, Option<Instant>)>> =
+        Mutex::new(VecDeque::new());
+}
+
+// where synthetic events go. the daemon sends them to the os through rdev,
+// tests record them instead so they can run without a display.
+pub trait EventSink: Send + Sync {
+    fn send(&self, event_type: EventType);
+}
+
+pub struct RdevSink;
+
+impl EventSink for RdevSink {
+    fn send(&self, event_type: EventType) {
+        send(event_type);
+    }
+}
+
+pub fn send(event_type: EventType) {
+    INJECTED
+        .lock()
+        .expect("injected was poisoned")
+        .push_back((event_type, None));
+
+    if let Err(err) = simulate(&event_type) {
+        eprintln!("error simulating event {:?}: {:?}", event_type, err);
+    }
+    thread::sleep(SEND_DELAY);
+}
+
+// called when the grab callback returns, the events sent so far can come back
+// from now on. events sent from other threads are only covered by the next
+// callback, which just keeps them around a little longer.
+pub fn settle() {
+    let now = Instant::now();
+    for (_, settled_at) in INJECTED.lock().expect("injected was poisoned").iter_mut() {
+        settled_at.get_or_insert(now);
+    }
+}
+
+fn expired(settled_at: Option<Instant>) -> bool {
+    settled_at.is_some_and(|settled_at| settled_at.elapsed() >= INJECTED_TTL)
+}
+
+pub fn is_injected(event_type: &EventType) -> bool {
+    let mut injected = INJECTED.lock().expect("injected was poisoned");
+    injected.retain(|(_, settled_at)| !expired(*settled_at));
+
+    let Some(position) = injected.iter().position(|(e, _)| e == event_type) else {
+        return false;
+    };
+    injected.remove(position);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::{expired, INJECTED_TTL};
+
+    #[test]
+    fn injected_events_only_expire_once_settled() {
+        let long_ago = Instant::now() - INJECTED_TTL * 2;
+        assert!(!expired(None));
+        assert!(!expired(Some(Instant::now())));
+        assert!(expired(Some(long_ago)));
+    }
+}
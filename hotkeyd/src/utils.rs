@@ -1,4 +1,4 @@
-use std::process::Command;
+use std::{env::var, process::Command};
 
 pub fn get_user() -> Option<String> {
     let Ok(o) = Command::new("/usr/bin/stat")
@@ -15,5 +15,71 @@ pub fn get_user() -> Option<String> {
         eprintln!("error converting stdout to string");
         return None;
     };
-    return Some(o.replace(|c| !char::is_alphabetic(c), ""));
+    Some(o.replace(|c| !char::is_alphabetic(c), ""))
+}
+
+// expands a leading `~` and every `$VAR` or `${VAR}` in a path from the
+// config. a variable that isn't set is an error instead of an empty string
+// so `$HOEM/bin` doesn't quietly turn into `/bin`.
+pub fn expand_path(path: &str) -> Result<String, String> {
+    let lookup =
+        |name: &str| var(name).map_err(|_| format!("`${}` in `{}` is not set", name, path));
+
+    let mut expanded = String::new();
+    let mut rest = path;
+    if rest == "~" || rest.starts_with("~/") {
+        expanded.push_str(&lookup("HOME")?);
+        rest = &rest[1..];
+    }
+
+    while let Some(start) = rest.find('$') {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let (name, len) = match after.strip_prefix('{') {
+            Some(braced) => {
+                let Some(end) = braced.find('}') else {
+                    return Err(format!("unclosed `${{` in `{}`", path));
+                };
+                (&braced[..end], end + 2)
+            }
+            None => {
+                let end = after
+                    .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                    .unwrap_or(after.len());
+                (&after[..end], end)
+            }
+        };
+        // a lone `$` is kept as is
+        if name.is_empty() && len == 0 {
+            expanded.push('$');
+        } else {
+            expanded.push_str(&lookup(name)?);
+        }
+        rest = &after[len..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env::var;
+
+    use super::expand_path;
+
+    #[test]
+    fn paths_are_expanded() {
+        let home = var("HOME").unwrap();
+        assert_eq!(expand_path("~/bin").unwrap(), format!("{}/bin", home));
+        assert_eq!(
+            expand_path("$HOME/x${HOME}").unwrap(),
+            format!("{0}/x{0}", home)
+        );
+        assert_eq!(expand_path("a~/$ b").unwrap(), "a~/$ b");
+        assert_eq!(
+            expand_path("$HOTKEYD_NOT_SET/x").unwrap_err(),
+            "`$HOTKEYD_NOT_SET` in `$HOTKEYD_NOT_SET/x` is not set"
+        );
+        assert!(expand_path("${HOME").is_err());
+    }
 }
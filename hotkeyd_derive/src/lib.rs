@@ -1,36 +1,195 @@
 extern crate proc_macro;
-use convert_case::Casing;
+use std::collections::HashMap;
+
+use convert_case::{Case, Casing};
 use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
 use quote::quote;
-use syn::{parse_macro_input, DeriveInput, Data, Fields};
+use syn::{parse_macro_input, Data, DeriveInput, Error, Fields, Ident, LitStr, Variant};
+
+// the variants of an enum that either has no payload or a single unnamed one
+fn enum_variants<'a>(input: &'a DeriveInput, derive: &str) -> syn::Result<Vec<&'a Variant>> {
+    let Data::Enum(data_enum) = &input.data else {
+        return Err(Error::new_spanned(
+            &input.ident,
+            format!("{} can only be derived for enums", derive),
+        ));
+    };
+
+    for variant in data_enum.variants.iter() {
+        match &variant.fields {
+            Fields::Unit => {}
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {}
+            fields => {
+                return Err(Error::new_spanned(
+                    fields,
+                    format!(
+                        "{} only supports unit variants and variants with a single unnamed field",
+                        derive
+                    ),
+                ))
+            }
+        }
+    }
+    Ok(data_enum.variants.iter().collect())
+}
+
+// a variant and every name it goes by in the config. the first name is the
+// one it is printed with.
+struct ConfigVariant<'a> {
+    ident: &'a Ident,
+    payload: bool,
+    names: Vec<(String, Span)>,
+}
+
+impl<'a> ConfigVariant<'a> {
+    fn new(variant: &'a Variant) -> syn::Result<Self> {
+        let mut name = (
+            variant.ident.to_string().to_case(Case::Kebab),
+            variant.ident.span(),
+        );
+        let mut aliases = Vec::new();
+
+        for attr in variant.attrs.iter() {
+            if !attr.path().is_ident("config") {
+                continue;
+            }
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    let rename: LitStr = meta.value()?.parse()?;
+                    name = (rename.value(), rename.span());
+                    Ok(())
+                } else if meta.path.is_ident("alias") {
+                    let alias: LitStr = meta.value()?.parse()?;
+                    aliases.push((alias.value(), alias.span()));
+                    Ok(())
+                } else {
+                    Err(meta.error("expected `rename = \"...\"` or `alias = \"...\"`"))
+                }
+            })?;
+        }
+
+        let mut names = vec![name];
+        names.extend(aliases);
+        for (name, span) in names.iter() {
+            if name.is_empty() || name.contains(':') || name.trim() != name {
+                return Err(Error::new(
+                    *span,
+                    format!("`{}` can't be written in the config", name),
+                ));
+            }
+        }
+
+        Ok(Self {
+            ident: &variant.ident,
+            payload: !matches!(variant.fields, Fields::Unit),
+            names,
+        })
+    }
 
-#[proc_macro_derive(ConfigKebabCase)]
+    fn name(&self) -> &str {
+        &self.names[0].0
+    }
+}
+
+// two variants with the same name would make one of them impossible to write
+fn check_collisions(variants: &[ConfigVariant]) -> syn::Result<()> {
+    let mut seen: HashMap<&str, &Ident> = HashMap::new();
+    let mut errors: Option<Error> = None;
+    for variant in variants {
+        for (name, span) in variant.names.iter() {
+            match seen.get(name.as_str()) {
+                Some(other) => {
+                    let error = Error::new(
+                        *span,
+                        format!("`{}` is already the config name of `{}`", name, other),
+                    );
+                    match errors.as_mut() {
+                        Some(errors) => errors.combine(error),
+                        None => errors = Some(error),
+                    }
+                }
+                None => {
+                    seen.insert(name, variant.ident);
+                }
+            }
+        }
+    }
+    errors.map_or(Ok(()), Err)
+}
+
+#[proc_macro_derive(ConfigKebabCase, attributes(config))]
 pub fn config_kebab_case(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
+    config_kebab_case_impl(&input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
 
-    let name = input.ident;
-
-    let variants = match input.data {
-        Data::Enum(data_enum) => data_enum.variants,
-        _ => panic!("EnumIndex can only be derived for enums"),
-    };
+fn config_kebab_case_impl(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let variants = enum_variants(input, "ConfigKebabCase")?
+        .into_iter()
+        .map(ConfigVariant::new)
+        .collect::<syn::Result<Vec<_>>>()?;
+    check_collisions(&variants)?;
 
     let variant_arms = variants.iter().map(|variant| {
-        let variant_name = &variant.ident;
-        let kebab_case_name = format!("{}", variant_name).to_case(convert_case::Case::Kebab);
-        
-        match &variant.fields {
-            Fields::Unit => quote! {
-                #kebab_case_name => Some(#name::#variant),
-            },
-            Fields::Unnamed(_) => quote! {
-                #kebab_case_name => Some(#name::#variant_name(0)),
-            },
-            _ => panic!("Only unit and unnamed variants are supported in EnumIndex"),
+        let variant_name = variant.ident;
+        let names = variant.names.iter().map(|(name, _)| name);
+
+        if variant.payload {
+            // tuple variants carry their payload after a colon, e.g. `unknown:123`
+            let prefixes = variant.names.iter().map(|(name, _)| format!("{}:", name));
+            quote! {
+                #(
+                    _ if config_name.starts_with(#prefixes) => {
+                        config_name[#prefixes.len()..].parse().ok().map(#name::#variant_name)
+                    }
+                )*
+            }
+        } else {
+            quote! {
+                #(#names)|* => Some(#name::#variant_name),
+            }
         }
     });
 
-    let expanded = quote! {
+    // tuple variants are named without their payload
+    let name_arms = variants.iter().map(|variant| {
+        let variant_name = variant.ident;
+        let config_name = variant.name();
+        if variant.payload {
+            quote! { #name::#variant_name(_) => #config_name, }
+        } else {
+            quote! { #name::#variant_name => #config_name, }
+        }
+    });
+
+    // like `to_config_kebab` but with the payload of tuple variants, so that
+    // it parses back to the same variant
+    let display_arms = variants.iter().map(|variant| {
+        let variant_name = variant.ident;
+        let config_name = variant.name();
+        if variant.payload {
+            quote! {
+                #name::#variant_name(payload) => write!(f, "{}:{}", #config_name, payload),
+            }
+        } else {
+            quote! { #name::#variant_name => f.write_str(#config_name), }
+        }
+    });
+
+    // tuple variants have a payload so they can't be listed
+    let unit_variants = variants
+        .iter()
+        .filter(|variant| !variant.payload)
+        .map(|variant| {
+            let variant_name = variant.ident;
+            quote! { #name::#variant_name }
+        });
+
+    Ok(quote! {
         impl #name {
             pub fn from_config_kebab(config_name: &str) -> Option<#name> {
                 match config_name {
@@ -38,56 +197,90 @@ pub fn config_kebab_case(input: TokenStream) -> TokenStream {
                     _ => None
                 }
             }
+
+            pub fn to_config_kebab(&self) -> &'static str {
+                match self {
+                    #(#name_arms)*
+                }
+            }
+
+            pub const ALL_VARIANTS: &'static [#name] = &[#(#unit_variants),*];
+        }
+
+        impl ::std::fmt::Display for #name {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                match self {
+                    #(#display_arms)*
+                }
+            }
         }
-    };
 
-    TokenStream::from(expanded)
+        impl ::std::str::FromStr for #name {
+            type Err = String;
+
+            fn from_str(config_name: &str) -> Result<Self, Self::Err> {
+                #name::from_config_kebab(config_name)
+                    .ok_or_else(|| format!("unknown {} `{}`", stringify!(#name), config_name))
+            }
+        }
+    })
 }
 
 #[proc_macro_derive(EnumIndex)]
 pub fn enum_index(input: TokenStream) -> TokenStream {
-    // Parse the input tokens into a syntax tree
     let input = parse_macro_input!(input as DeriveInput);
+    enum_index_impl(&input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
 
-    // Get the name of the enum
-    let name = input.ident;
-
-    // Match the input to ensure it's an enum
-    let variants = match input.data {
-        Data::Enum(data_enum) => data_enum.variants,
-        _ => panic!("EnumIndex can only be derived for enums"),
-    };
+fn enum_index_impl(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let variants = enum_variants(input, "EnumIndex")?;
 
-    // Generate discriminant function by assigning indexes to enum variants
-    let mut index: u32 = 0;
-    let variant_arms = variants.iter().map(|variant| {
+    // variants are numbered in the order they are declared
+    let variant_arms = variants.iter().enumerate().map(|(index, variant)| {
         let variant_name = &variant.ident;
-        let current_index = index;
-        index += 1;
-        
+        let index = index as u32;
         match &variant.fields {
             Fields::Unit => quote! {
-                #name::#variant_name => #current_index
+                #name::#variant_name => #index
             },
-            // For `Unknown(u32)` or similar, you can still return a discriminant value
-            Fields::Unnamed(_) => quote! {
-                #name::#variant_name(_) => #current_index
+            // for `Unknown(u32)` or similar the payload doesn't matter
+            _ => quote! {
+                #name::#variant_name(_) => #index
             },
-            _ => panic!("Only unit and unnamed variants are supported in EnumIndex"),
         }
     });
 
-    // Generate the final index function
-    let expanded = quote! {
+    // tuple variants can't be made from their index alone
+    let from_index_arms = variants.iter().enumerate().filter_map(|(index, variant)| {
+        let variant_name = &variant.ident;
+        let index = index as u32;
+        match &variant.fields {
+            Fields::Unit => Some(quote! { #index => Some(#name::#variant_name), }),
+            _ => None,
+        }
+    });
+
+    let count = variants.len() as u32;
+
+    Ok(quote! {
         impl #name {
+            pub const COUNT: u32 = #count;
+
             pub fn index(&self) -> u32 {
                 match self {
                     #(#variant_arms),*
                 }
             }
-        }
-    };
 
-    // Convert the generated code into a TokenStream
-    TokenStream::from(expanded)
+            pub fn from_index(index: u32) -> Option<#name> {
+                match index {
+                    #(#from_index_arms)*
+                    _ => None,
+                }
+            }
+        }
+    })
 }
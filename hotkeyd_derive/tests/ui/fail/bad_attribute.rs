@@ -0,0 +1,9 @@
+use hotkeyd_derive::ConfigKebabCase;
+
+#[derive(ConfigKebabCase)]
+enum Key {
+    #[config(name = "a")]
+    KeyA,
+}
+
+fn main() {}
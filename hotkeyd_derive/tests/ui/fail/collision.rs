@@ -0,0 +1,12 @@
+use hotkeyd_derive::ConfigKebabCase;
+
+#[derive(ConfigKebabCase)]
+enum Key {
+    ControlLeft,
+    #[config(alias = "control-left")]
+    Ctrl,
+    #[config(rename = "ctrl")]
+    Control,
+}
+
+fn main() {}
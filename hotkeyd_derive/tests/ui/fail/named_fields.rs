@@ -0,0 +1,9 @@
+use hotkeyd_derive::ConfigKebabCase;
+
+#[derive(ConfigKebabCase)]
+enum Key {
+    KeyA,
+    Unknown { code: u32 },
+}
+
+fn main() {}
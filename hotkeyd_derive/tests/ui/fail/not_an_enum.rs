@@ -0,0 +1,8 @@
+use hotkeyd_derive::EnumIndex;
+
+#[derive(EnumIndex)]
+struct Key {
+    code: u32,
+}
+
+fn main() {}
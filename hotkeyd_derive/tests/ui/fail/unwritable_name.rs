@@ -0,0 +1,9 @@
+use hotkeyd_derive::ConfigKebabCase;
+
+#[derive(ConfigKebabCase)]
+enum Key {
+    #[config(rename = "key:b")]
+    KeyB,
+}
+
+fn main() {}
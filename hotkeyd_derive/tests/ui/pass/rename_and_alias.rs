@@ -0,0 +1,30 @@
+use hotkeyd_derive::{ConfigKebabCase, EnumIndex};
+
+#[derive(Debug, PartialEq, EnumIndex, ConfigKebabCase)]
+enum Key {
+    KeyA,
+    #[config(rename = "ctrl", alias = "control", alias = "ctl")]
+    ControlLeft,
+    #[config(rename = "code")]
+    Unknown(u32),
+}
+
+fn main() {
+    assert_eq!(Key::from_config_kebab("key-a"), Some(Key::KeyA));
+    assert_eq!(Key::from_config_kebab("ctrl"), Some(Key::ControlLeft));
+    assert_eq!(Key::from_config_kebab("control"), Some(Key::ControlLeft));
+    assert_eq!(Key::from_config_kebab("ctl"), Some(Key::ControlLeft));
+    assert_eq!(Key::from_config_kebab("control-left"), None);
+    assert_eq!(Key::from_config_kebab("code:7"), Some(Key::Unknown(7)));
+    assert_eq!(Key::from_config_kebab("unknown:7"), None);
+
+    assert_eq!(Key::ControlLeft.to_string(), "ctrl");
+    assert_eq!(Key::Unknown(7).to_config_kebab(), "code");
+    assert_eq!(Key::Unknown(7).to_string(), "code:7");
+    assert_eq!(Key::ALL_VARIANTS, &[Key::KeyA, Key::ControlLeft]);
+    assert_eq!("ctl".parse(), Ok(Key::ControlLeft));
+    assert_eq!(Key::Unknown(7).index(), 2);
+    assert_eq!(Key::COUNT, 3);
+    assert_eq!(Key::from_index(1), Some(Key::ControlLeft));
+    assert_eq!(Key::from_index(2), None);
+}